@@ -1,16 +1,18 @@
-use guppy::graph::{DependencyDirection, PackageGraph, PackageLink};
+use guppy::graph::{
+    BuildTargetId, BuildTargetKind, DependencyDirection, PackageGraph, PackageLink,
+};
 use guppy::{MetadataCommand, PackageId};
-use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::{
     hash_map::{Entry, HashMap},
-    HashSet,
+    BTreeMap, BTreeSet, HashSet,
 };
 use std::iter::FromIterator;
 use std::path::PathBuf;
 use tempdir::TempDir;
 
-use crate::metrics;
+use crate::http::HttpClient;
+use crate::{metrics, notes, review, scan, verify, vet};
 
 //
 // Essential Structs
@@ -21,20 +23,24 @@ use crate::metrics;
 /// Note that the word "total" means that it includes transitive dependencies.
 #[rustfmt::skip]
 #[derive(Default, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct PackageRisk {
 
   // metadata
   // --------
 
   /// name of the dependency
-  #[serde(skip)]
   pub name: String,
-  /// potentially different versions are pulled (bad)
-  pub versions: HashSet<String>,
+  /// version of the dependency (the analysis is done per `name@version`)
+  pub versions: BTreeSet<String>,
   /// link to its repository
   pub repo: Option<String>,
   /// description from Cargo.toml
   pub description: Option<String>,
+  /// license expression from Cargo.toml
+  pub license: Option<String>,
+  /// authors from Cargo.toml
+  pub authors: Vec<String>,
 
   // useful for analysis
   // -------------------
@@ -53,13 +59,25 @@ pub struct PackageRisk {
   pub internal: bool,
   /// is this dependency used for the host target and features?
   pub used: bool,
+  /// is this a procedural macro (executed at compile time)?
+  pub is_proc_macro: bool,
+  /// minimum supported rust version declared in Cargo.toml (`rust-version`)
+  pub rust_version: Option<String>,
+  /// rust edition of the package
+  pub edition: String,
+  /// how the package is imported by the root crates ("normal", "build" and/or "dev")
+  pub dependency_kinds: BTreeSet<String>,
   
   /// direct dependencies
-  pub direct_dependencies: HashSet<String>,
+  pub direct_dependencies: BTreeSet<String>,
+  /// number of direct dependencies whose requirement doesn't accept their latest version
+  pub stale_own_deps: u64,
   /// transitive dependencies (not including this dependency)
-  pub transitive_dependencies: HashSet<String>,
+  pub transitive_dependencies: BTreeSet<String>,
   /// number of root crates that import this package
   pub root_importers: Vec<String>,
+  /// the shortest chains of imports from each root crate to this package
+  pub import_paths: Vec<Vec<String>>,
   /// total number of transitive third party dependencies imported
   /// by this dependency, and only by this dependency
   pub exclusive_deps_introduced: Vec<String>,
@@ -72,14 +90,269 @@ pub struct PackageRisk {
   /// (total) number of lines of unsafe code
   pub unsafe_loc: u64,
   pub total_unsafe_loc: u64,
-  /// number of github stars, if any
+  /// number of unsafe blocks, functions, impls and traits
+  pub unsafe_usage: UnsafeUsage,
+  /// where the unsafe code is
+  pub unsafe_locations: Vec<UnsafeLocation>,
+  /// does the crate forbid unsafe code (`#![forbid(unsafe_code)]` or `#![deny(unsafe_code)]`)?
+  pub forbids_unsafe: bool,
+  /// sub-directory of the repository containing the crate (for monorepos)
+  pub repo_sub_path: Option<String>,
+  /// normalized URL of the repository (without `git+`, `.git`, links to sub-directories, etc.)
+  pub repo_url: Option<String>,
+  /// number of stars on github/gitlab/gitea (watchers on bitbucket), if any
   pub stargazers_count: Option<u64>,
+  /// date of the last commit on the default branch of the repo
+  pub last_commit: Option<String>,
+  /// has the repo been archived? (it won't receive any fix)
+  pub upstream_archived: bool,
+  /// if the repo is a fork, the repo it was forked from
+  pub upstream_fork_of: Option<String>,
   /// active contributors on github (in the last 6 months)
   pub active_contributors: Option<u64>,
+  /// number of contributors on github (capped at 100)
+  pub contributors: Option<u64>,
+  /// share of the commits authored by the top contributor (between 0 and 1)
+  pub bus_factor: Option<f64>,
+  /// number of open issues on github
+  pub open_issues: Option<u64>,
+  /// number of open pull requests on github
+  pub open_pull_requests: Option<u64>,
+  /// median time (in hours) to get a first response on the 20 most recent github issues
+  pub median_response_time: Option<f64>,
+  /// does the github repo have continuous integration set up?
+  pub has_ci: Option<bool>,
+  /// is the crate fuzzed? (fuzz directory in the github repo, or OSS-Fuzz project)
+  pub has_fuzzing: Option<bool>,
+  /// does the github repo have a SECURITY.md (vulnerability disclosure policy)?
+  pub security_policy: Option<bool>,
+  /// does the github repo have a code of conduct?
+  pub code_of_conduct: Option<bool>,
+  /// does the github repo have funding metadata (.github/FUNDING.yml)?
+  pub funding: Option<bool>,
+  /// did docs.rs manage to build the documentation of the versions pulled?
+  pub docs_built: Option<bool>,
+  /// SourceRank score from libraries.io (if a key was given)
+  pub sourcerank: Option<u64>,
   /// number of dependent crates on crates.io
   pub crates_io_dependent: Option<u64>,
+  /// newest (non-yanked, non-prerelease) version on crates.io
+  pub latest_version: Option<String>,
+  /// how far behind the latest version the oldest version pulled is:
+  /// number of newer semver-incompatible series, newer minor versions, and newer patch versions
+  pub major_versions_behind: Option<u64>,
+  pub minor_versions_behind: Option<u64>,
+  pub patch_versions_behind: Option<u64>,
+  /// date of the latest release on crates.io
+  pub latest_release: Option<String>,
+  /// average number of days between releases over the last year (none if no release)
+  pub release_interval: Option<f64>,
+  /// number of downloads on crates.io
+  pub downloads_total: Option<u64>,
+  /// number of downloads on crates.io in the last 90 days
+  pub downloads_recent: Option<u64>,
   /// last update according to crates.io
   pub crates_io_last_updated: Option<String>,
+  /// does the repository have a tag for the versions pulled? (e.g. `v1.2.3`)
+  pub version_tag_found: Option<bool>,
+  /// owners of the crate on crates.io (users and teams)
+  pub owners: Vec<String>,
+  /// set if the most recent version was published by someone who never published it before
+  pub new_publisher: Option<String>,
+  /// is one of the versions pulled yanked from crates.io?
+  pub yanked: bool,
+  /// how the version pulled was published on crates.io
+  pub provenance: Option<Provenance>,
+  /// metrics that couldn't be obtained because an API rate limited us
+  pub rate_limited_metrics: Vec<String>,
+  /// known security advisories affecting the versions pulled (from RustSec)
+  pub known_advisories: Vec<Advisory>,
+  /// have all the versions pulled been audited (according to cargo-vet)?
+  pub vetted: bool,
+  /// what reviewers wrote about the crate (in `dephell-notes.toml`)
+  pub reviewer_notes: Option<notes::ReviewerNotes>,
+  /// have the versions pulled been signed off (according to `--review-state`)?
+  pub review: Option<review::Review>,
+  /// differences between the code on crates.io and the code on the repository
+  pub source_verification: Option<verify::SourceVerification>,
+  /// the popular crate this crate's name is suspiciously close to (typosquatting)
+  pub typosquatting_suspect: Option<String>,
+  /// sha256 checksum of the .crate of each version pulled (from Cargo.lock)
+  pub checksums: BTreeMap<String, String>,
+  /// does the source in the registry cache match the checksum of Cargo.lock?
+  pub checksum_verified: Option<bool>,
+  /// size of the published .crate archive, in bytes
+  pub crate_size: Option<u64>,
+  /// number of files in the published .crate archive
+  pub crate_file_count: Option<u64>,
+  /// precompiled binaries and opaque blobs shipped in the package
+  pub binary_artifacts: Vec<String>,
+  /// source files that look obfuscated (long lines, high-entropy literals, etc.)
+  pub suspicious_files: Vec<String>,
+  /// dangerous operations performed by the build script (if there is one)
+  pub build_script_risk: Option<scan::BuildScriptRisk>,
+  /// native code linked or bundled (for -sys crates and the like)
+  pub native_code: Option<scan::NativeCode>,
+  /// can this package access the network?
+  pub network_access: bool,
+  /// sensitive capabilities used by this package ("network", "process", "exec", "filesystem_write")
+  pub capabilities: Vec<String>,
+  /// locations (`file:line`) of the code accessing the network
+  pub network_evidence: Vec<String>,
+  /// items exporting symbols (`#[no_mangle]`, `#[export_name]`, `#[link_section]`)
+  pub symbol_exports: Vec<String>,
+  /// composite score of how risky the package is (the higher, the riskier)
+  pub risk_score: u64,
+}
+
+/// UnsafeUsage counts the different uses of the `unsafe` keyword in a package.
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+pub struct UnsafeUsage {
+    /// `unsafe { ... }`
+    pub blocks: u64,
+    /// `unsafe { ... }` preceded by a `// SAFETY:` comment
+    pub documented_blocks: u64,
+    /// `unsafe fn`
+    pub functions: u64,
+    /// `unsafe impl`
+    pub impls: u64,
+    /// `unsafe trait`
+    pub traits: u64,
+    /// calls to `mem::transmute`
+    pub transmutes: u64,
+    /// `static mut` declarations
+    pub static_muts: u64,
+    /// dereferences in unsafe code, and `ptr::read`/`ptr::write` calls (likely raw pointers)
+    pub raw_pointer_derefs: u64,
+    /// `unsafe impl Send` and `unsafe impl Sync`
+    pub send_sync_impls: u64,
+    /// FFI `extern` blocks
+    pub extern_blocks: u64,
+}
+
+/// UnsafeLocation is the location of unsafe code in a package.
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+pub struct UnsafeLocation {
+    /// path of the file (relative to the package)
+    pub file: String,
+    /// first line of the unsafe code
+    pub line: usize,
+    /// number of lines of the unsafe code
+    pub span: usize,
+    /// "block", "function", "impl", "trait" or "extern"
+    pub kind: String,
+}
+
+/// Provenance describes how a version was published on crates.io.
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+pub struct Provenance {
+    /// was it published from CI via trusted publishing (verifiable),
+    /// or with an API token (possibly from someone's machine)?
+    pub trusted_publishing: bool,
+    /// the crates.io user who published it with an API token
+    pub published_by: Option<String>,
+    /// the CI provider that published it (e.g. "github")
+    pub ci_provider: Option<String>,
+    /// the repository the publishing CI workflow ran in
+    pub ci_repository: Option<String>,
+    /// the commit the publishing CI workflow ran on
+    pub ci_commit: Option<String>,
+}
+
+/// Advisory is a security advisory affecting a version of a package.
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+pub struct Advisory {
+    /// identifier of the advisory (e.g. RUSTSEC-2020-0001)
+    pub id: String,
+    /// severity (`low`, `medium`, `high` or `critical`), if any
+    pub severity: Option<String>,
+    /// version requirements that are not affected by the advisory
+    pub patched_versions: Vec<String>,
+    /// other identifiers of the same vulnerability (CVE, GHSA, RUSTSEC, etc.)
+    #[serde(default)]
+    pub aliases: Vec<String>,
+}
+
+impl Advisory {
+    /// checks if two advisories are about the same vulnerability
+    /// (e.g. a RustSec advisory and its GHSA mirror, which share a CVE or list each other)
+    pub fn is_same(&self, other: &Advisory) -> bool {
+        self.id == other.id
+            || self.aliases.contains(&other.id)
+            || other.aliases.contains(&self.id)
+            || self
+                .aliases
+                .iter()
+                .any(|alias| other.aliases.contains(alias))
+    }
+}
+
+impl PackageRisk {
+    /// adds an advisory, unless it's already known (under this identifier or another one)
+    fn add_advisory(&mut self, advisory: Advisory) {
+        if !self
+            .known_advisories
+            .iter()
+            .any(|known| known.is_same(&advisory))
+        {
+            self.known_advisories.push(advisory);
+        }
+    }
+
+    /// sorts the lists that are filled in no particular order
+    fn sort_lists(&mut self) {
+        self.root_importers.sort();
+        self.exclusive_deps_introduced.sort();
+        self.owners.sort();
+        self.rate_limited_metrics.sort();
+        self.known_advisories.sort_by(|a, b| a.id.cmp(&b.id));
+    }
+
+    /// a composite score of how risky the package is (the higher, the riskier),
+    /// adding up points for each risk signal found
+    pub fn compute_risk_score(&self) -> u64 {
+        let mut score = 0;
+        // advisories without a severity count as high
+        for advisory in &self.known_advisories {
+            score += match advisory.severity.as_deref() {
+                Some("critical") | Some("high") | None => 40,
+                Some("medium") => 20,
+                Some(_) => 10,
+            };
+        }
+        if self.yanked {
+            score += 30;
+        }
+        if self.checksum_verified == Some(false) {
+            score += 50;
+        }
+        if self.typosquatting_suspect.is_some() {
+            score += 30;
+        }
+        // a point per 10 lines of unsafe code, up to 30
+        score += (self.unsafe_loc / 10).min(30);
+        if !self.suspicious_files.is_empty() {
+            score += 20;
+        }
+        if !self.binary_artifacts.is_empty() {
+            score += 10;
+        }
+        if let Some(build_script_risk) = &self.build_script_risk {
+            let risky = build_script_risk.spawns_processes
+                || build_script_risk.network_access
+                || !build_script_risk.sensitive_env_vars.is_empty()
+                || build_script_risk.writes_outside_out_dir;
+            if risky {
+                score += 10;
+            }
+        }
+        if self.upstream_archived {
+            score += 15;
+        }
+        // a point per dependency introduced only by this package, up to 10
+        score += (self.exclusive_deps_introduced.len() as u64).min(10);
+        score
+    }
 }
 
 //
@@ -87,6 +360,27 @@ pub struct PackageRisk {
 // ------
 //
 
+/// identifies a package in the result: `name@version`
+/// (guppy's PackageIds are long and opaque, and several versions of a crate can be pulled)
+pub fn package_key(name: &str, version: &semver::Version) -> String {
+    format!("{}@{}", name, version)
+}
+
+/// records `metrics` as skipped if a query was given up because of rate limiting
+/// since `rate_limit_hits` was obtained
+fn record_rate_limited(
+    package_risk: &mut PackageRisk,
+    http_client: &HttpClient,
+    rate_limit_hits: usize,
+    metrics: &[&str],
+) {
+    if http_client.rate_limit_hits() > rate_limit_hits {
+        package_risk
+            .rate_limited_metrics
+            .extend(metrics.iter().map(|metric| metric.to_string()));
+    }
+}
+
 fn create_or_update_dependency(
     analysis_result: &mut HashMap<PackageId, PackageRisk>,
     dep_link: &PackageLink,
@@ -106,20 +400,55 @@ fn create_or_update_dependency(
                 .insert(dep_link.to().version().to_string());
             package_risk.repo = dep_link.to().repository().map(|x| x.to_owned());
             package_risk.description = dep_link.to().description().map(|x| x.to_owned());
+            package_risk.license = dep_link.to().license().map(|x| x.to_owned());
+            package_risk.authors = dep_link.to().authors().to_vec();
             package_risk.manifest_path = dep_link.to().manifest_path().to_path_buf();
             package_risk.internal = dep_link.to().in_workspace();
+            package_risk.is_proc_macro = dep_link
+                .to()
+                .build_target(&BuildTargetId::Library)
+                .map(|build_target| build_target.kind() == &BuildTargetKind::ProcMacro)
+                .unwrap_or(false);
             entry.insert(package_risk);
         }
     };
 }
 
+/// AnalysisOptions configures what `analyze_repo` analyzes and how.
+#[derive(Default)]
+pub struct AnalysisOptions<'a> {
+    /// a github personnal access token (PAT) used to query the github API,
+    /// this is useful due to github limiting queries that are not authenticated.
+    pub github_token: Option<&'a str>,
+    /// base URL of the github API (for GitHub Enterprise), defaults to api.github.com
+    pub github_api_url: Option<&'a str>,
+    /// the host of a self-hosted gitlab instance (gitlab.com is always supported)
+    pub gitlab_host: Option<&'a str>,
+    /// a libraries.io API key, to obtain the SourceRank of dependencies
+    pub librariesio_key: Option<&'a str>,
+    /// the only workspace packages to analyze
+    pub packages: Option<Vec<&'a str>>,
+    /// a list of workspace packages to ignore
+    pub to_ignore: Option<Vec<&'a str>>,
+    /// also query OSV.dev for vulnerabilities missing from RustSec
+    pub osv: bool,
+    /// path to a cargo-vet `audits.toml` file
+    pub vet_audits: Option<&'a str>,
+    /// diff the code published on crates.io with the code on the repository (slow)
+    pub verify_source: bool,
+    /// suppress any output to stdout
+    pub quiet: bool,
+    /// called with each package (and its `name@version`) as soon as its metrics are obtained,
+    /// the metrics that depend on the whole tree (total LOC, stale requirements,
+    /// OSV advisories) are only in the final result
+    pub on_package: Option<&'a dyn Fn(&str, &PackageRisk)>,
+}
+
 /// Takes a `manifest_path` and produce an analysis stored in `analysis_result`.
 ///
-/// Optionally, you can pass:
-/// - `proxy`, a proxy (used to query github to fetch number of stars)
-/// - `github_token`, a github personnal access token (PAT) used to query the github API
-///   this is useful due to github limiting queries that are not authenticated.
-/// - `to_ignore`, a list of direct dependencies to ignore.
+/// The `http_client` is used for every external query (github, crates.io, etc.),
+/// configure it with a proxy if needed.
+/// The rest of the analysis can be configured via `options`.
 ///
 /// Let's define some useful terms as well:
 /// - **workspace packages** or **root crates**: crates that live in the workspace
@@ -132,19 +461,18 @@ fn create_or_update_dependency(
 ///
 pub fn analyze_repo(
     manifest_path: &str,
-    http_client: reqwest::blocking::Client,
-    github_token: Option<(&str, &str)>,
-    packages: Option<Vec<&str>>,
-    to_ignore: Option<Vec<&str>>,
-    quiet: bool,
+    http_client: HttpClient,
+    options: &AnalysisOptions,
 ) -> Result<
     (
-        HashSet<String>,              // root_crates
-        HashSet<String>,              // main_dependencies
-        HashMap<String, PackageRisk>, // analysis_result
+        BTreeSet<String>,              // root_crates
+        BTreeSet<String>,              // main_dependencies
+        BTreeMap<String, PackageRisk>, // analysis_result
     ),
     String,
 > {
+    let quiet = options.quiet;
+
     //
     // Obtain package graph via guppy
     // ------------------------------
@@ -179,7 +507,7 @@ pub fn analyze_repo(
     let root_crates: HashSet<PackageId> = HashSet::from_iter(root_crates);
     let mut root_crates_to_analyze: HashSet<PackageId> = root_crates.clone();
     // either select specific packages or remove ignored packages
-    if let Some(packages) = packages {
+    if let Some(packages) = &options.packages {
         root_crates_to_analyze = root_crates_to_analyze
             .into_iter()
             .filter(|pkg_id| {
@@ -188,7 +516,7 @@ pub fn analyze_repo(
                 packages.contains(&package_name)
             })
             .collect();
-    } else if let Some(to_ignore) = to_ignore {
+    } else if let Some(to_ignore) = &options.to_ignore {
         root_crates_to_analyze = root_crates_to_analyze
             .into_iter()
             .filter(|pkg_id| {
@@ -212,7 +540,7 @@ pub fn analyze_repo(
     // TODO: combine the two loops and inline `create_or_update...`
     // find all direct dependencies
     let mut main_dependencies_ids: HashSet<PackageId> = HashSet::new();
-    let mut main_dependencies: HashSet<String> = HashSet::new();
+    let mut main_dependencies: BTreeSet<String> = BTreeSet::new();
     for root_crate in &root_crates_to_analyze {
         // (non-ignored) root crate > direct dependency
         let dep_links = package_graph
@@ -223,7 +551,7 @@ pub fn analyze_repo(
             .filter(|dep_link| !dep_link.dev_only());
         for dep_link in dep_links {
             main_dependencies_ids.insert(dep_link.to().id().to_owned());
-            main_dependencies.insert(dep_link.to().name().to_string());
+            main_dependencies.insert(package_key(dep_link.to().name(), dep_link.to().version()));
             create_or_update_dependency(&mut analysis_result, &dep_link);
         }
     }
@@ -247,7 +575,7 @@ pub fn analyze_repo(
     let target_dir = TempDir::new("target_dir").expect("could not create temporary folder");
     let target_dir = target_dir.path();
     let output = std::process::Command::new("cargo")
-        .env("RUSTFLAGS", "-Funsafe-code  --cap-lints=warn")
+        .env("RUSTFLAGS", "--cap-lints=warn")
         .args(&[
             "check",
             "-vv",
@@ -266,34 +594,51 @@ pub fn analyze_repo(
         return Err("Could not build the target manifest path.".to_string());
     }
 
-    // .unsafe_loc - find unsafe by analyzing the compiler's output
-    let output = std::io::Cursor::new(output.stdout);
-    for message in cargo_metadata::Message::parse_stream(output) {
-        match message {
-            Ok(cargo_metadata::Message::CompilerMessage(msg)) => {
-                if let Some(code) = msg.message.code {
-                    if code.code == "unsafe_code" {
-                        let package_id = PackageId::new(msg.package_id.repr);
-                        analysis_result
-                            .entry(package_id)
-                            .and_modify(|r| r.unsafe_loc += 1);
-                    }
-                }
-            }
-            _ => (),
-        }
-    }
-
     // TODO: find # of panic
 
     /*
     cargo clippy -vv --message-format=json-diagnostic-rendered-ansi -- -Fclippy::panic --cap-lints=warn 2>/dev/null | egrep '^\{' | jq -r 'select(.message?.code?.code? == "clippy::panic").message?.rendered?'
     */
 
+    // Parse cargo-vet audits (if given)
+    // ---------------------------------
+    //
+
+    let vetted_versions = match options.vet_audits {
+        Some(vet_audits) => Some(vet::parse_audits(vet_audits)?),
+        None => None,
+    };
+
+    // Parse Cargo.lock checksums
+    // --------------------------
+    //
+
+    let lockfile_path = package_graph.workspace().root().join("Cargo.lock");
+    let lockfile_checksums = metrics::parse_lockfile_checksums(&lockfile_path);
+
+    // Fetch the RustSec advisory database
+    // -----------------------------------
+    //
+
+    let advisory_db = match rustsec::Database::fetch() {
+        Ok(advisory_db) => Some(advisory_db),
+        Err(err) => {
+            eprintln!("dephell: could not fetch the RustSec advisory database");
+            eprintln!("{}", err);
+            None
+        }
+    };
+
     // Analyze!
     // --------
     //
 
+    let dependency_kinds = metrics::get_dependency_kinds(&package_graph, &root_crates_to_analyze);
+    let github = options.github_token.map(|token| metrics::GithubApi {
+        token,
+        api_url: options.github_api_url.unwrap_or(metrics::GITHUB_API_URL),
+    });
+
     for (package_id, mut package_risk) in analysis_result.iter_mut() {
         // .direct_dependencies
         package_risk.direct_dependencies = package_graph
@@ -301,7 +646,7 @@ pub fn analyze_repo(
             .unwrap()
             .direct_links()
             .filter(|dep_link| !dep_link.dev_only())
-            .map(|dep_link| dep_link.to().name().to_string())
+            .map(|dep_link| package_key(dep_link.to().name(), dep_link.to().version()))
             .collect();
 
         // .transitive_dependencies
@@ -311,14 +656,23 @@ pub fn analyze_repo(
             .resolve()
             .links(DependencyDirection::Forward)
             .filter(|dep_link| !dep_link.dev_only())
-            .map(|dep_link| dep_link.to().name().to_string())
+            .map(|dep_link| package_key(dep_link.to().name(), dep_link.to().version()))
             .collect();
 
+        // .dependency_kinds
+        if let Some(kinds) = dependency_kinds.get(package_id) {
+            package_risk.dependency_kinds = kinds.clone();
+        }
+
         // .root_importers
         let root_importers =
             metrics::get_root_importers(&package_graph, &root_crates_to_analyze, package_id);
         package_risk.root_importers = root_importers;
 
+        // .import_paths
+        package_risk.import_paths =
+            metrics::get_import_paths(&package_graph, &root_crates_to_analyze, package_id);
+
         // .exclusive_deps_introduced
         let exclusive_deps_introduced =
             metrics::get_exclusive_deps(&package_graph, &root_crates_to_analyze, package_id);
@@ -335,41 +689,505 @@ pub fn analyze_repo(
         // .loc + .rust_loc
         metrics::get_loc(&mut package_risk, &dependency_files);
 
+        // .unsafe_loc + .unsafe_usage
+        let package_path = package_risk.manifest_path.parent().unwrap().to_path_buf();
+        let rust_files = scan::parse_rust_files(&package_path, &dependency_files);
+        metrics::get_unsafe(&mut package_risk, &rust_files);
+
+        // .forbids_unsafe
+        let crate_root = package_graph
+            .metadata(package_id)
+            .unwrap()
+            .build_target(&BuildTargetId::Library)
+            .map(|build_target| build_target.path().to_path_buf());
+        if let Some(crate_root) = crate_root {
+            package_risk.forbids_unsafe = scan::forbids_unsafe(&crate_root);
+        }
+
+        // .binary_artifacts + .suspicious_files
+        if !package_risk.internal {
+            let package_path = package_risk.manifest_path.parent().unwrap();
+            package_risk.binary_artifacts = scan::get_binary_artifacts(package_path);
+            package_risk.suspicious_files = scan::get_suspicious_files(package_path);
+        }
+
+        // .capabilities + .network_access
+        if !package_risk.internal {
+            let mut capabilities = scan::get_capabilities(&rust_files);
+            package_risk.capabilities = capabilities
+                .keys()
+                .map(|capability| capability.to_string())
+                .collect();
+            if let Some(network_evidence) = capabilities.remove(scan::NETWORK) {
+                package_risk.network_access = true;
+                package_risk.network_evidence = network_evidence;
+            }
+        }
+
+        // .symbol_exports
+        if !package_risk.internal {
+            package_risk.symbol_exports = scan::get_symbol_exports(&rust_files);
+        }
+
+        // .native_code
+        if !package_risk.internal {
+            let links = package_graph.metadata(package_id).unwrap().links();
+            let package_path = package_risk.manifest_path.parent().unwrap();
+            package_risk.native_code =
+                scan::get_native_code(&package_risk.name, links, package_path);
+        }
+
+        // .build_script_risk
+        if !package_risk.internal {
+            let build_script = package_graph
+                .metadata(package_id)
+                .unwrap()
+                .build_target(&BuildTargetId::BuildScript)
+                .map(|build_target| build_target.path().to_path_buf());
+            if let Some(build_script) = build_script {
+                package_risk.build_script_risk = scan::get_build_script_risk(&build_script);
+            }
+        }
+
+        // .rust_version + .edition
+        let (rust_version, edition) =
+            metrics::get_toolchain_requirements(&package_risk.manifest_path);
+        package_risk.rust_version = rust_version;
+        package_risk.edition = edition;
+
+        // .checksum_verified
+        for version in &package_risk.versions {
+            let key = (package_risk.name.clone(), version.clone());
+            if let Some(expected_checksum) = lockfile_checksums.get(&key) {
+                package_risk
+                    .checksums
+                    .insert(version.clone(), expected_checksum.clone());
+                let verified =
+                    metrics::verify_checksum(&package_risk.name, version, expected_checksum);
+                // a single mismatch is enough to flag the package
+                if verified.is_some() && package_risk.checksum_verified != Some(false) {
+                    package_risk.checksum_verified = verified;
+                }
+            }
+        }
+
+        // .crate_size + .crate_file_count
+        // (only available if the .crate is in the cargo registry cache)
+        for version in &package_risk.versions {
+            if let Some((size, file_count)) =
+                metrics::get_crate_archive_stats(&package_risk.name, version)
+            {
+                package_risk.crate_size = Some(size);
+                package_risk.crate_file_count = Some(file_count);
+            }
+        }
+
+        // .typosquatting_suspect
+        if !package_risk.internal {
+            package_risk.typosquatting_suspect =
+                metrics::get_typosquatting_suspect(&package_risk.name);
+        }
+
+        // .vetted
+        if let Some(vetted_versions) = &vetted_versions {
+            package_risk.vetted = package_risk
+                .versions
+                .iter()
+                .all(|version| vet::is_vetted(vetted_versions, &package_risk.name, version));
+        }
+
+        // .known_advisories
+        if let Some(advisory_db) = &advisory_db {
+            let mut advisories = Vec::new();
+            for version in &package_risk.versions {
+                advisories.extend(metrics::get_rustsec_advisories(
+                    advisory_db,
+                    &package_risk.name,
+                    version,
+                ));
+            }
+            for advisory in advisories {
+                package_risk.add_advisory(advisory);
+            }
+        }
+
+        // .repo_sub_path
+        let repo_url = package_risk
+            .repo
+            .as_ref()
+            .and_then(|repo| metrics::normalize_repo_url(repo));
+        if let Some(repo_url) = &repo_url {
+            package_risk.repo_sub_path = repo_url.sub_path.clone();
+            package_risk.repo_url = Some(repo_url.url());
+        }
+
+        // .stargazers_count + .last_commit + .upstream_archived + .upstream_fork_of
+        let rate_limit_hits = http_client.rate_limit_hits();
+        if let Some(repo_url) = &repo_url {
+            let repo_host = metrics::get_repo_host(repo_url, github, options.gitlab_host);
+            let repo_stats = repo_host
+                .and_then(|repo_host| repo_host.get_repo_stats(http_client.clone(), repo_url));
+            if let Some(repo_stats) = repo_stats {
+                package_risk.stargazers_count = repo_stats.stars;
+                package_risk.last_commit = repo_stats.last_commit;
+                package_risk.upstream_archived = repo_stats.archived;
+                package_risk.upstream_fork_of = repo_stats.fork_of;
+            }
+        }
+        record_rate_limited(
+            &mut package_risk,
+            &http_client,
+            rate_limit_hits,
+            &[
+                "stargazers_count",
+                "last_commit",
+                "upstream_archived",
+                "upstream_fork_of",
+            ],
+        );
+
         // is this a github repo?
-        if let Some(repo_url) = &package_risk.repo {
-            if let Some(github_token) = github_token {
-                let re = Regex::new(r"github\.com/([a-zA-Z0-9._-]*/[a-zA-Z0-9._-]*)").unwrap();
-                if let Some(repo_name) = re
-                    .captures(repo_url)
-                    .and_then(|caps| caps.get(1))
-                    .map(|m| m.as_str())
-                {
-                    // .stargazers_count
-                    let stars =
-                        metrics::get_github_stars(http_client.clone(), github_token, &repo_name);
-                    package_risk.stargazers_count = stars;
-
-                    // .active_contributors
-                    let active_contributors = metrics::get_active_maintainers(
-                        http_client.clone(),
-                        github_token,
-                        &repo_name,
-                    );
-                    package_risk.active_contributors = active_contributors;
+        let rate_limit_hits = http_client.rate_limit_hits();
+        if let (Some(repo_url), Some(github)) = (&repo_url, &github) {
+            if github.hosts(repo_url) {
+                let repo_name = repo_url.full_name();
+
+                // .active_contributors
+                let active_contributors =
+                    metrics::get_active_maintainers(http_client.clone(), github, &repo_name);
+                package_risk.active_contributors = active_contributors;
+
+                // .contributors + .bus_factor
+                let contributors =
+                    metrics::get_github_contributors(http_client.clone(), github, &repo_name);
+                if let Some((contributors, bus_factor)) = contributors {
+                    package_risk.contributors = Some(contributors);
+                    package_risk.bus_factor = Some(bus_factor);
+                }
+
+                // .open_issues + .open_pull_requests + .median_response_time
+                let activity =
+                    metrics::get_github_activity(http_client.clone(), github, &repo_name);
+                if let Some(activity) = activity {
+                    package_risk.open_issues = Some(activity.open_issues);
+                    package_risk.open_pull_requests = Some(activity.open_pull_requests);
+                    package_risk.median_response_time = activity.median_response_time;
+                }
+
+                // .has_ci + .has_fuzzing
+                let ci_and_fuzzing = metrics::get_ci_and_fuzzing(
+                    http_client.clone(),
+                    github,
+                    repo_url,
+                    &package_risk.name,
+                );
+                if let Some((has_ci, has_fuzzing)) = ci_and_fuzzing {
+                    package_risk.has_ci = Some(has_ci);
+                    package_risk.has_fuzzing = Some(has_fuzzing);
+                }
+
+                // .security_policy + .code_of_conduct + .funding
+                let hygiene = metrics::get_repo_hygiene(http_client.clone(), github, &repo_name);
+                if let Some(hygiene) = hygiene {
+                    package_risk.security_policy = Some(hygiene.security_policy);
+                    package_risk.code_of_conduct = Some(hygiene.code_of_conduct);
+                    package_risk.funding = Some(hygiene.funding);
+                }
+
+                // .known_advisories (from GHSA)
+                let advisories = metrics::get_github_advisories(
+                    http_client.clone(),
+                    github,
+                    &package_risk.name,
+                    &package_risk.versions,
+                );
+                for advisory in advisories.unwrap_or_default() {
+                    package_risk.add_advisory(advisory);
+                }
+            }
+        }
+        record_rate_limited(
+            &mut package_risk,
+            &http_client,
+            rate_limit_hits,
+            &[
+                "active_contributors",
+                "contributors",
+                "bus_factor",
+                "open_issues",
+                "open_pull_requests",
+                "median_response_time",
+                "has_ci",
+                "has_fuzzing",
+                "security_policy",
+                "code_of_conduct",
+                "funding",
+                "known_advisories",
+            ],
+        );
+
+        // .version_tag_found (only with --verify-source, as it runs git for every dependency)
+        if let (Some(repo_url), false, true) =
+            (&repo_url, package_risk.internal, options.verify_source)
+        {
+            for version in &package_risk.versions {
+                match verify::find_version_tag(&repo_url.url(), &package_risk.name, version) {
+                    Ok(found) => {
+                        let all_found = package_risk.version_tag_found.unwrap_or(true);
+                        package_risk.version_tag_found = Some(all_found && found);
+                    }
+                    Err(err) => eprintln!("dephell: {}", err),
+                }
+            }
+        }
+
+        // .source_verification
+        if options.verify_source && !package_risk.internal {
+            if let (Some(repo_url), Some(version)) =
+                (&repo_url, package_risk.versions.iter().next())
+            {
+                match verify::verify_source(
+                    http_client.clone(),
+                    &package_risk.name,
+                    version,
+                    &repo_url.url(),
+                ) {
+                    Ok(verification) => package_risk.source_verification = Some(verification),
+                    Err(err) => eprintln!(
+                        "dephell: could not verify the source of {}: {}",
+                        package_risk.name, err
+                    ),
                 }
             }
         }
 
         // .crates_io_dependent
         // TODO: do not make a request to crates.io if this is not a crates.io dep
+        let rate_limit_hits = http_client.rate_limit_hits();
         let crates_io_dependent =
             metrics::get_crates_io_dependent(http_client.clone(), &package_risk.name);
         package_risk.crates_io_dependent = crates_io_dependent;
+        record_rate_limited(
+            &mut package_risk,
+            &http_client,
+            rate_limit_hits,
+            &["crates_io_dependent"],
+        );
 
-        // .crates_io_dependent
-        let crates_io_last_updated =
-            metrics::get_crates_io_last_updated(http_client.clone(), &package_risk.name);
-        package_risk.crates_io_last_updated = crates_io_last_updated;
+        // .crates_io_last_updated + .downloads_total + .downloads_recent + .new_publisher
+        // + .latest_release + .release_interval + .latest_version + .*_versions_behind
+        let rate_limit_hits = http_client.rate_limit_hits();
+        let crates_io_crate = metrics::get_crates_io_crate(http_client.clone(), &package_risk.name);
+        if let Some(crates_io_crate) = crates_io_crate {
+            package_risk.new_publisher = metrics::get_new_publisher(&crates_io_crate.versions);
+            let (latest_release, release_interval) =
+                metrics::get_release_cadence(&crates_io_crate.versions);
+            package_risk.latest_release = latest_release;
+            package_risk.release_interval = release_interval;
+
+            // fall back on crates.io for the size of the .crate, if it wasn't cached
+            if package_risk.crate_size.is_none() {
+                package_risk.crate_size = crates_io_crate
+                    .versions
+                    .iter()
+                    .filter(|published| package_risk.versions.contains(&published.num))
+                    .filter_map(|published| published.crate_size)
+                    .max();
+            }
+
+            // compare the oldest version pulled with the latest
+            let oldest_version = package_risk
+                .versions
+                .iter()
+                .filter_map(|version| semver::Version::parse(version).ok())
+                .min();
+            let versions_behind = oldest_version.and_then(|oldest_version| {
+                metrics::get_versions_behind(&crates_io_crate.versions, &oldest_version.to_string())
+            });
+            if let Some(versions_behind) = versions_behind {
+                package_risk.latest_version = Some(versions_behind.latest);
+                package_risk.major_versions_behind = Some(versions_behind.major);
+                package_risk.minor_versions_behind = Some(versions_behind.minor);
+                package_risk.patch_versions_behind = Some(versions_behind.patch);
+            }
+            package_risk.crates_io_last_updated = Some(crates_io_crate.updated_at);
+            package_risk.downloads_total = Some(crates_io_crate.downloads);
+            package_risk.downloads_recent = crates_io_crate.recent_downloads;
+        }
+        record_rate_limited(
+            &mut package_risk,
+            &http_client,
+            rate_limit_hits,
+            &[
+                "crates_io_last_updated",
+                "downloads_total",
+                "downloads_recent",
+                "new_publisher",
+                "latest_release",
+                "release_interval",
+                "latest_version",
+            ],
+        );
+
+        // .docs_built
+        if !package_risk.internal {
+            let rate_limit_hits = http_client.rate_limit_hits();
+            for version in &package_risk.versions {
+                let docs_built =
+                    metrics::get_docs_rs_status(http_client.clone(), &package_risk.name, version);
+                // a single failed build is enough to flag the package
+                if docs_built.is_some() && package_risk.docs_built != Some(false) {
+                    package_risk.docs_built = docs_built;
+                }
+            }
+            record_rate_limited(
+                &mut package_risk,
+                &http_client,
+                rate_limit_hits,
+                &["docs_built"],
+            );
+        }
+
+        // .sourcerank
+        if let (Some(api_key), false) = (options.librariesio_key, package_risk.internal) {
+            let rate_limit_hits = http_client.rate_limit_hits();
+            package_risk.sourcerank = metrics::get_librariesio_sourcerank(
+                http_client.clone(),
+                api_key,
+                &package_risk.name,
+            );
+            record_rate_limited(
+                &mut package_risk,
+                &http_client,
+                rate_limit_hits,
+                &["sourcerank"],
+            );
+        }
+
+        // .owners
+        if !package_risk.internal {
+            let rate_limit_hits = http_client.rate_limit_hits();
+            let owners = metrics::get_crates_io_owners(http_client.clone(), &package_risk.name);
+            package_risk.owners = owners.unwrap_or_default();
+            record_rate_limited(
+                &mut package_risk,
+                &http_client,
+                rate_limit_hits,
+                &["owners"],
+            );
+        }
+
+        // .yanked + .provenance
+        let rate_limit_hits = http_client.rate_limit_hits();
+        if !package_risk.internal {
+            for version in &package_risk.versions {
+                let crates_io_version = metrics::get_crates_io_version(
+                    http_client.clone(),
+                    &package_risk.name,
+                    version,
+                );
+                if let Some(crates_io_version) = crates_io_version {
+                    if crates_io_version.yanked {
+                        package_risk.yanked = true;
+                    }
+                    let trustpub_data = crates_io_version.trustpub_data;
+                    package_risk.provenance = Some(Provenance {
+                        trusted_publishing: trustpub_data.is_some(),
+                        published_by: crates_io_version.published_by.map(|user| user.login),
+                        ci_provider: trustpub_data.as_ref().map(|data| data.provider.clone()),
+                        ci_repository: trustpub_data
+                            .as_ref()
+                            .and_then(|data| data.repository.clone()),
+                        ci_commit: trustpub_data.and_then(|data| data.sha),
+                    });
+                }
+            }
+        }
+        record_rate_limited(
+            &mut package_risk,
+            &http_client,
+            rate_limit_hits,
+            &["yanked", "provenance"],
+        );
+
+        // stream the package
+        if let Some(on_package) = options.on_package {
+            let package_metadata = package_graph.metadata(package_id).unwrap();
+            let key = package_key(package_metadata.name(), package_metadata.version());
+            on_package(&key, package_risk);
+        }
+    }
+
+    // Stale requirements
+    // ------------------
+    // requirements of a dependency's own Cargo.toml that don't accept the latest version
+    // of what they require (deps.rs-style)
+
+    let latest_versions: HashMap<PackageId, semver::Version> = analysis_result
+        .iter()
+        .filter_map(|(package_id, package_risk)| {
+            let latest_version = package_risk.latest_version.as_ref()?;
+            let latest_version = semver::Version::parse(latest_version).ok()?;
+            Some((package_id.clone(), latest_version))
+        })
+        .collect();
+    for (package_id, package_risk) in analysis_result.iter_mut() {
+        if package_risk.internal {
+            continue;
+        }
+        let stale_own_deps: HashSet<&str> = package_graph
+            .metadata(package_id)
+            .unwrap()
+            .direct_links()
+            .filter(|dep_link| !dep_link.dev_only())
+            .filter(|dep_link| {
+                latest_versions
+                    .get(dep_link.to().id())
+                    .map(|latest_version| !dep_link.version_req().matches(latest_version))
+                    .unwrap_or(false)
+            })
+            .map(|dep_link| dep_link.to().name())
+            .collect();
+        package_risk.stale_own_deps = stale_own_deps.len() as u64;
+    }
+
+    // OSV advisories
+    // --------------
+    // merge vulnerabilities from OSV.dev that we didn't get from RustSec
+
+    if options.osv {
+        let mut queries: Vec<(PackageId, String, String)> = Vec::new();
+        for (package_id, package_risk) in &analysis_result {
+            for version in &package_risk.versions {
+                queries.push((
+                    package_id.clone(),
+                    package_risk.name.clone(),
+                    version.clone(),
+                ));
+            }
+        }
+        let packages: Vec<(String, String)> = queries
+            .iter()
+            .map(|(_, name, version)| (name.clone(), version.clone()))
+            .collect();
+        if let Some(results) = metrics::get_osv_vulnerability_ids(http_client.clone(), &packages) {
+            for ((package_id, _, _), vuln_ids) in queries.iter().zip(results) {
+                let package_risk = analysis_result.get_mut(package_id).unwrap();
+                for vuln_id in vuln_ids {
+                    let already_known = package_risk.known_advisories.iter().any(|advisory| {
+                        advisory.id == vuln_id || advisory.aliases.contains(&vuln_id)
+                    });
+                    if already_known {
+                        continue;
+                    }
+                    if let Some(advisory) =
+                        metrics::get_osv_vulnerability(http_client.clone(), &vuln_id)
+                    {
+                        package_risk.add_advisory(advisory);
+                    }
+                }
+            }
+        }
     }
 
     // total LOC
@@ -441,21 +1259,24 @@ pub fn analyze_repo(
         // next
     }
 
-    // PackageId -> name
-    // -----------------
-    // this is useful because PackageIds are long strings,
-    // and we only care about package names for the result
-    let root_crates_to_analyze: HashSet<String> = root_crates_to_analyze
-        .iter()
-        .map(|pkg_id| {
-            let package_metadata = package_graph.metadata(pkg_id).unwrap();
-            package_metadata.name().to_owned()
+    // PackageId -> name@version
+    // -------------------------
+    // this is useful because PackageIds are long opaque strings,
+    // the result is keyed by `name@version` so that it can be consumed without guppy
+    let key = |pkg_id: &PackageId| {
+        let package_metadata = package_graph.metadata(pkg_id).unwrap();
+        package_key(package_metadata.name(), package_metadata.version())
+    };
+    // (sorted, so that two runs on the same lockfile give the same output)
+    let root_crates_to_analyze: BTreeSet<String> = root_crates_to_analyze.iter().map(key).collect();
+    let analysis_result: BTreeMap<String, PackageRisk> = analysis_result
+        .into_iter()
+        .map(|(package_id, mut package_risk)| {
+            package_risk.sort_lists();
+            package_risk.risk_score = package_risk.compute_risk_score();
+            (key(&package_id), package_risk)
         })
         .collect();
-    let analysis_result: HashMap<String, PackageRisk> = analysis_result
-        .iter()
-        .map(|(_, package_risk)| (package_risk.name.clone(), package_risk.clone()))
-        .collect();
 
     //
     Ok((root_crates_to_analyze, main_dependencies, analysis_result))
@@ -0,0 +1,175 @@
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+//
+// Configuration file
+// ==================
+// a `dephell.toml` next to Cargo.toml gives defaults for the command-line arguments,
+// with the same names, e.g.
+//
+// ```toml
+// ignore-workspace = ["xtask"]
+// output = ["html=dephell.html", "json=dephell.json"]
+// osv = true
+// ```
+//
+
+/// where the configuration is looked for by default
+pub const CONFIG_FILE: &str = "dephell.toml";
+
+/// arguments that decide where requests (and the tokens) are sent, or what gets executed:
+/// a `dephell.toml` found in the analyzed tree can't set them,
+/// as anyone able to change the tree (e.g. a pull request) could leak the CI token
+const SENSITIVE_ARGS: &[&str] = &[
+    "config",
+    "github-token",
+    "github-token-env",
+    "github-api-url",
+    "gitlab-host",
+    "librariesio-key",
+    "proxy",
+    "template",
+];
+
+/// the other names of the arguments (shorts and aliases),
+/// to know which arguments of the configuration are overridden on the command line
+const OTHER_NAMES: &[(&str, &str)] = &[
+    ("-m", "manifest-path"),
+    ("-p", "package"),
+    ("-o", "output"),
+    ("-i", "ignore-workspace"),
+    ("-q", "quiet"),
+    ("--compare-to", "baseline"),
+];
+
+/// returns true if the argument `name` is given on the command line
+fn is_given(args: &[OsString], name: &str) -> bool {
+    let long = format!("--{}", name);
+    let mut names: Vec<&str> = OTHER_NAMES
+        .iter()
+        .filter(|(_, other)| *other == name)
+        .map(|(other_name, _)| *other_name)
+        .collect();
+    names.push(&long);
+    args.iter().filter_map(|arg| arg.to_str()).any(|arg| {
+        names.iter().any(|name| {
+            if name.starts_with("--") {
+                arg == *name || arg.starts_with(&format!("{}=", name))
+            } else {
+                // short names can be followed by their value (`-mCargo.toml`)
+                !arg.starts_with("--") && arg.starts_with(name)
+            }
+        })
+    })
+}
+
+/// returns the value of an argument given on the command line, before it's parsed
+/// (it's needed to find the configuration, which has to be parsed with the rest of the arguments)
+fn given_value(args: &[OsString], long: &str, short: Option<&str>) -> Option<String> {
+    let long = format!("--{}", long);
+    let args: Vec<&str> = args.iter().filter_map(|arg| arg.to_str()).collect();
+    for (i, arg) in args.iter().enumerate() {
+        if *arg == long || Some(*arg) == short {
+            return args.get(i + 1).map(|value| value.to_string());
+        }
+        if arg.starts_with(&format!("{}=", long)) {
+            return Some(arg[long.len() + 1..].to_owned());
+        }
+        if let Some(short) = short {
+            if arg.starts_with(short) && !arg.starts_with("--") {
+                return Some(arg[short.len()..].to_owned());
+            }
+        }
+    }
+    None
+}
+
+/// converts a value of the configuration to the arguments it stands for
+fn to_args(name: &str, value: &toml::Value, args: &mut Vec<OsString>) -> Result<(), String> {
+    match value {
+        toml::Value::Boolean(true) => args.push(format!("--{}", name).into()),
+        toml::Value::Boolean(false) => (),
+        toml::Value::String(value) => args.push(format!("--{}={}", name, value).into()),
+        toml::Value::Integer(value) => args.push(format!("--{}={}", name, value).into()),
+        toml::Value::Float(value) => args.push(format!("--{}={}", name, value).into()),
+        toml::Value::Array(values) => {
+            for value in values {
+                to_args(name, value, args)?;
+            }
+        }
+        _ => {
+            return Err(format!(
+                "{} must be a boolean, a string, a number or a list",
+                name
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// Config is the configuration file used, if any
+pub struct Config {
+    pub path: PathBuf,
+    /// given with `--config` (otherwise, found next to Cargo.toml)
+    pub explicit: bool,
+}
+
+/// finds the configuration: the one given with `--config`,
+/// or `dephell.toml` next to Cargo.toml (or in the current directory)
+pub fn find_config(args: &[OsString]) -> Option<Config> {
+    if let Some(config_path) = given_value(args, "config", None) {
+        return Some(Config {
+            path: PathBuf::from(config_path),
+            explicit: true,
+        });
+    }
+    let dir = match given_value(args, "manifest-path", Some("-m")) {
+        Some(manifest_path) => Path::new(&manifest_path).parent()?.to_path_buf(),
+        None => std::env::current_dir().ok()?,
+    };
+    Some(dir.join(CONFIG_FILE))
+        .filter(|config_path| config_path.exists())
+        .map(|path| Config {
+            path,
+            explicit: false,
+        })
+}
+
+/// merges the arguments of the configuration with the ones of the command line,
+/// so that they're validated together (the ones of the command line take precedence)
+pub fn merge_args(config: &Config, args: Vec<OsString>) -> Result<Vec<OsString>, String> {
+    let error = |err: String| {
+        format!(
+            "dephell: could not parse {}: {}",
+            config.path.display(),
+            err
+        )
+    };
+    let contents = fs::read_to_string(&config.path)
+        .map_err(|err| format!("dephell: could not read {}: {}", config.path.display(), err))?;
+    let values: toml::value::Table =
+        toml::from_str(&contents).map_err(|err| error(err.to_string()))?;
+
+    let mut config_args = Vec::new();
+    for (name, value) in &values {
+        if !config.explicit && SENSITIVE_ARGS.contains(&name.as_str()) {
+            eprintln!(
+                "dephell: ignoring {} in {} (give it on the command line, or the file with --config)",
+                name,
+                config.path.display()
+            );
+            continue;
+        }
+        if !is_given(&args, name) {
+            to_args(name, value, &mut config_args).map_err(error)?;
+        }
+    }
+
+    // the arguments of the configuration go right after the name of the program
+    let mut args = args.into_iter();
+    let mut merged: Vec<OsString> = args.next().into_iter().collect();
+    merged.extend(config_args);
+    merged.extend(args);
+    Ok(merged)
+}
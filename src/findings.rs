@@ -0,0 +1,151 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::analysis::{Advisory, PackageRisk};
+
+//
+// Findings
+// ========
+// risky things found during the analysis, as a flat list,
+// used by the machine-readable output formats (SARIF, etc.)
+//
+
+/// number of unsafe lines of code above which a dependency is flagged
+pub const HIGH_UNSAFE_LOC: u64 = 100;
+
+/// how serious a finding is (same levels as SARIF)
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Level {
+    Note,
+    Warning,
+    Error,
+}
+
+impl Level {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Note => "note",
+            Level::Warning => "warning",
+            Level::Error => "error",
+        }
+    }
+}
+
+/// Rule describes a kind of finding
+#[derive(Debug)]
+pub struct Rule {
+    /// stable identifier of the rule
+    pub id: &'static str,
+    /// short description of the rule
+    pub description: &'static str,
+    /// default level of the findings
+    pub level: Level,
+}
+
+pub const HIGH_UNSAFE: Rule = Rule {
+    id: "DEPHELL001",
+    description: "dependency with a lot of unsafe code",
+    level: Level::Warning,
+};
+
+pub const KNOWN_ADVISORY: Rule = Rule {
+    id: "DEPHELL002",
+    description: "dependency affected by a security advisory",
+    level: Level::Error,
+};
+
+pub const UNUSED_DEPENDENCY: Rule = Rule {
+    id: "DEPHELL003",
+    description: "direct dependency not used for the host target and features",
+    level: Level::Note,
+};
+
+pub const YANKED_VERSION: Rule = Rule {
+    id: "DEPHELL004",
+    description: "dependency version yanked from crates.io",
+    level: Level::Error,
+};
+
+/// every rule, in order
+pub const RULES: &[&Rule] = &[
+    &HIGH_UNSAFE,
+    &KNOWN_ADVISORY,
+    &UNUSED_DEPENDENCY,
+    &YANKED_VERSION,
+];
+
+/// Finding is a rule violated by a dependency
+#[derive(Debug)]
+pub struct Finding {
+    pub rule: &'static Rule,
+    pub level: Level,
+    /// the dependency (`name@version`, as in the analysis result)
+    pub package_id: String,
+    /// name of the dependency
+    pub package: String,
+    /// versions of the dependency pulled
+    pub versions: Vec<String>,
+    pub message: String,
+    /// the advisory, for findings of `KNOWN_ADVISORY`
+    pub advisory: Option<Advisory>,
+}
+
+/// lists the findings of an analysis, sorted by package and rule
+pub fn get_findings(
+    main_dependencies: &BTreeSet<String>,
+    analysis_result: &BTreeMap<String, PackageRisk>,
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for (package_id, package_risk) in analysis_result {
+        if package_risk.internal {
+            continue;
+        }
+        let name = &package_risk.name;
+        let mut versions: Vec<String> = package_risk.versions.iter().cloned().collect();
+        versions.sort();
+        let mut push =
+            |rule: &'static Rule, level: Level, message: String, advisory: Option<&Advisory>| {
+                findings.push(Finding {
+                    rule,
+                    level,
+                    package_id: package_id.clone(),
+                    package: name.clone(),
+                    versions: versions.clone(),
+                    message,
+                    advisory: advisory.cloned(),
+                })
+            };
+
+        if package_risk.unsafe_loc > HIGH_UNSAFE_LOC {
+            let message = format!(
+                "{} has {} lines of unsafe code",
+                name, package_risk.unsafe_loc
+            );
+            push(&HIGH_UNSAFE, HIGH_UNSAFE.level, message, None);
+        }
+
+        for advisory in &package_risk.known_advisories {
+            // advisories without a high severity are only warnings
+            let level = match advisory.severity.as_deref() {
+                Some("critical") | Some("high") | None => KNOWN_ADVISORY.level,
+                Some(_) => Level::Warning,
+            };
+            let message = match &advisory.severity {
+                Some(severity) => format!("{} is affected by {} ({})", name, advisory.id, severity),
+                None => format!("{} is affected by {}", name, advisory.id),
+            };
+            push(&KNOWN_ADVISORY, level, message, Some(advisory));
+        }
+
+        if !package_risk.used && main_dependencies.contains(package_id) {
+            let message = format!("{} is not used for the host target and features", name);
+            push(&UNUSED_DEPENDENCY, UNUSED_DEPENDENCY.level, message, None);
+        }
+
+        if package_risk.yanked {
+            let message = format!("{} {} was yanked from crates.io", name, versions.join(", "));
+            push(&YANKED_VERSION, YANKED_VERSION.level, message, None);
+        }
+    }
+    findings.sort_by(|a, b| (&a.package_id, a.rule.id).cmp(&(&b.package_id, b.rule.id)));
+    findings
+}
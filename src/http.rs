@@ -0,0 +1,301 @@
+use reqwest::header::{HeaderMap, AUTHORIZATION, ETAG, IF_NONE_MATCH, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+//
+// HTTP Client
+// ===========
+// every external query goes through here,
+// so that responses can be cached on disk with their ETags,
+// and so that rate limits are respected
+//
+
+/// number of times a request is retried (rate limited, server errors, network errors)
+const MAX_RETRIES: u32 = 3;
+
+/// we don't wait longer than this for a rate limit to reset,
+/// the host is skipped for the rest of the analysis instead
+const MAX_WAIT: Duration = Duration::from_secs(60);
+
+/// how long a cached response to a POST request (e.g. a GraphQL query) is reused,
+/// as it can't be revalidated with an ETag
+const POST_CACHE_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// CachedResponse is a response stored on disk
+#[derive(Serialize, Deserialize, Debug)]
+struct CachedResponse {
+    /// to revalidate the response to a GET request
+    #[serde(default)]
+    etag: Option<String>,
+    /// when the response was received (seconds since the epoch)
+    #[serde(default)]
+    fetched_at: u64,
+    body: String,
+}
+
+/// the current time, in seconds since the epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or_default()
+}
+
+/// Response is a response from an API (or from the cache, if the API said it didn't change)
+#[derive(Debug)]
+pub struct Response {
+    pub status: StatusCode,
+    pub body: String,
+}
+
+impl Response {
+    /// parses the body as JSON
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_str(&self.body)
+    }
+}
+
+/// HttpClient wraps a reqwest client, and caches responses on disk.
+/// On subsequent runs, conditional GET requests (If-None-Match) are sent,
+/// which don't count against github's rate limit when nothing changed,
+/// and the responses to POST requests are reused for a while.
+/// Requests that are rate limited are retried after the delay the API asks for.
+#[derive(Clone, Debug)]
+pub struct HttpClient {
+    client: reqwest::blocking::Client,
+    /// where to cache responses (no caching if `None`)
+    cache_dir: Option<PathBuf>,
+    /// number of requests that were given up because of rate limiting
+    rate_limit_hits: Arc<AtomicUsize>,
+    /// hosts that we stopped querying, until their rate limit resets
+    exhausted_hosts: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+/// returns the default cache directory (in `$XDG_CACHE_HOME` or `~/.cache`)
+pub fn default_cache_dir() -> Option<PathBuf> {
+    let cache_home = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(cache_home.join("cargo-dephell").join("http"))
+}
+
+/// is the response telling us that we're rate limited?
+fn is_rate_limited(status: StatusCode, headers: &HeaderMap) -> bool {
+    let remaining = headers
+        .get("x-ratelimit-remaining")
+        .and_then(|remaining| remaining.to_str().ok());
+    status == StatusCode::TOO_MANY_REQUESTS
+        || (status == StatusCode::FORBIDDEN
+            && (remaining == Some("0") || headers.contains_key(RETRY_AFTER)))
+}
+
+/// how long the API asks us to wait before retrying (`Retry-After` or `X-RateLimit-Reset`)
+fn requested_wait(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    if let Some(seconds) = header(RETRY_AFTER.as_str()).and_then(|value| value.parse().ok()) {
+        return Some(Duration::from_secs(seconds));
+    }
+    let reset: u64 = header("x-ratelimit-reset")?.parse().ok()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    Some(Duration::from_secs(reset.saturating_sub(now) + 1))
+}
+
+/// exponential backoff: 1s, 2s, 4s, etc.
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_secs(1 << attempt)
+}
+
+impl HttpClient {
+    pub fn new(client: reqwest::blocking::Client, cache_dir: Option<PathBuf>) -> Self {
+        let mut cache_dir = cache_dir;
+        if let Some(dir) = &cache_dir {
+            if let Err(err) = fs::create_dir_all(dir) {
+                eprintln!("dephell: can't create cache directory {:?}: {}", dir, err);
+                cache_dir = None;
+            }
+        }
+        Self {
+            client,
+            cache_dir,
+            rate_limit_hits: Arc::new(AtomicUsize::new(0)),
+            exhausted_hosts: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn get(&self, url: &str) -> reqwest::blocking::RequestBuilder {
+        self.client.get(url)
+    }
+
+    pub fn post(&self, url: &str) -> reqwest::blocking::RequestBuilder {
+        self.client.post(url)
+    }
+
+    /// number of requests given up so far because of rate limiting,
+    /// compare it before and after a query to know if it was rate limited
+    pub fn rate_limit_hits(&self) -> usize {
+        self.rate_limit_hits.load(Ordering::SeqCst)
+    }
+
+    /// where the response to a GET or POST request is cached
+    /// (the key is a hash of the URL, the body of a POST request, and the credentials,
+    /// so that authenticated responses are cached per token without storing it)
+    fn cache_path(&self, request: &reqwest::blocking::Request) -> Option<PathBuf> {
+        use sha2::{Digest, Sha256};
+        let cache_dir = self.cache_dir.as_ref()?;
+        let mut key = request.url().as_str().as_bytes().to_vec();
+        match *request.method() {
+            reqwest::Method::GET => (),
+            reqwest::Method::POST => {
+                key.extend_from_slice(b"\nPOST\n");
+                key.extend_from_slice(request.body()?.as_bytes()?);
+            }
+            _ => return None,
+        }
+        if let Some(authorization) = request.headers().get(AUTHORIZATION) {
+            key.push(b'\n');
+            key.extend_from_slice(authorization.as_bytes());
+        }
+        Some(cache_dir.join(format!("{:x}.json", Sha256::digest(&key))))
+    }
+
+    /// gives up on a request because of rate limiting,
+    /// and stops querying the host until its rate limit resets
+    fn give_up(&self, host: &str, wait: Duration) {
+        self.rate_limit_hits.fetch_add(1, Ordering::SeqCst);
+        let mut exhausted_hosts = self.exhausted_hosts.lock().unwrap();
+        if exhausted_hosts
+            .insert(host.to_owned(), Instant::now() + wait)
+            .is_none()
+        {
+            eprintln!(
+                "dephell: rate limited by {}, skipping it for the next {} minutes",
+                host,
+                wait.as_secs() / 60 + 1
+            );
+        }
+    }
+
+    /// sends a request and reads the response,
+    /// GET requests are sent conditionally if we have a cached response,
+    /// and requests are retried (with backoff) if they fail or are rate limited
+    pub fn send(&self, request: reqwest::blocking::RequestBuilder) -> reqwest::Result<Response> {
+        let mut request = request;
+        let mut attempt = 0;
+        loop {
+            // keep a copy of the request in case we need to send it again
+            let retry = request.try_clone();
+            let built = request.build()?;
+            let host = built.url().host_str().unwrap_or_default().to_owned();
+
+            // don't bother querying a host that is rate limiting us
+            let exhausted_until = self.exhausted_hosts.lock().unwrap().get(&host).cloned();
+            if let Some(exhausted_until) = exhausted_until {
+                if Instant::now() < exhausted_until {
+                    self.rate_limit_hits.fetch_add(1, Ordering::SeqCst);
+                    return Ok(Response {
+                        status: StatusCode::TOO_MANY_REQUESTS,
+                        body: format!("skipped: rate limited by {}", host),
+                    });
+                }
+            }
+
+            // send it, and figure out if we should retry
+            let result = self.send_once(built);
+            let can_retry = attempt < MAX_RETRIES && retry.is_some();
+            let wait = match &result {
+                Err(err) if err.is_timeout() || err.is_connect() => backoff(attempt),
+                Ok((resp, headers)) if is_rate_limited(resp.status, headers) => {
+                    let wait = requested_wait(headers).unwrap_or_else(|| backoff(attempt));
+                    if wait > MAX_WAIT || !can_retry {
+                        self.give_up(&host, wait);
+                        return result.map(|(resp, _)| resp);
+                    }
+                    wait
+                }
+                Ok((resp, _)) if resp.status.is_server_error() => backoff(attempt),
+                _ => return result.map(|(resp, _)| resp),
+            };
+
+            // retry
+            match retry {
+                Some(retry) if can_retry => {
+                    std::thread::sleep(wait);
+                    request = retry;
+                    attempt += 1;
+                }
+                _ => return result.map(|(resp, _)| resp),
+            }
+        }
+    }
+
+    /// sends a request once, using (and updating) the cache
+    fn send_once(
+        &self,
+        mut request: reqwest::blocking::Request,
+    ) -> reqwest::Result<(Response, HeaderMap)> {
+        // add the ETag of the cached response, if any
+        let cache_path = self.cache_path(&request);
+        let cached: Option<CachedResponse> = cache_path
+            .as_ref()
+            .and_then(|cache_path| fs::read_to_string(cache_path).ok())
+            .and_then(|cached| serde_json::from_str(&cached).ok());
+        let is_post = request.method() == reqwest::Method::POST;
+        if let Some(cached) = cached.as_ref() {
+            // a recent response to a POST request is reused as is
+            if is_post && now().saturating_sub(cached.fetched_at) < POST_CACHE_MAX_AGE.as_secs() {
+                let resp = Response {
+                    status: StatusCode::OK,
+                    body: cached.body.clone(),
+                };
+                return Ok((resp, HeaderMap::new()));
+            }
+            if let Some(etag) = cached.etag.as_ref().and_then(|etag| etag.parse().ok()) {
+                request.headers_mut().insert(IF_NONE_MATCH, etag);
+            }
+        }
+
+        // send
+        let resp = self.client.execute(request)?;
+        let status = resp.status();
+        let headers = resp.headers().clone();
+
+        // not modified: use the cache
+        if status == StatusCode::NOT_MODIFIED {
+            if let Some(cached) = cached {
+                let resp = Response {
+                    status: StatusCode::OK,
+                    body: cached.body,
+                };
+                return Ok((resp, headers));
+            }
+        }
+
+        // cache successful responses that have an ETag, and the responses to POST requests
+        let etag = headers
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(|etag| etag.to_owned());
+        let body = resp.text()?;
+        let cacheable = status.is_success() && (etag.is_some() || is_post);
+        if let (true, Some(cache_path)) = (cacheable, cache_path) {
+            let cached = CachedResponse {
+                etag,
+                fetched_at: now(),
+                body: body.clone(),
+            };
+            if let Ok(cached) = serde_json::to_string(&cached) {
+                if let Err(err) = fs::write(&cache_path, cached) {
+                    eprintln!("dephell: can't write to cache {:?}: {}", cache_path, err);
+                }
+            }
+        }
+
+        Ok((Response { status, body }, headers))
+    }
+}
@@ -1,46 +1,170 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
 use std::path::PathBuf;
 
-use askama::Template;
-use clap::{App, Arg};
+use clap::{App, Arg, SubCommand};
 use serde::{Deserialize, Serialize};
 
 mod analysis;
+mod config;
+mod findings;
+mod http;
 mod metrics;
+mod notes;
+mod output;
+mod policy;
+mod report;
+mod review;
+mod scan;
+mod serve;
+mod verify;
+mod vet;
 
 //
-// HTML Stuff
+// JSON Stuff
 // ==========
 //
 
-#[derive(Template)]
-#[template(path = "list.html", escape = "none")]
-struct HtmlList {
-    name: String,
-    // TODO: we might want to compress/base64 this to lighten the HTML output
-    json_result: String,
+/// version of the JSON output, bumped whenever its format changes
+const SCHEMA_VERSION: u32 = 2;
+
+/// JsonReport is the JSON output: the result of the analysis, and how it was obtained
+#[derive(Serialize, Deserialize)]
+struct JsonReport {
+    schema_version: u32,
+    metadata: RunMetadata,
+    result: JsonResult,
 }
 
-//
-// JSON Stuff
-// ==========
-//
+#[derive(Serialize, Deserialize)]
+struct RunMetadata {
+    /// version of cargo-dephell that produced the report
+    tool_version: String,
+    /// when the analysis was started (RFC 3339)
+    timestamp: String,
+    /// target triple the dependencies were resolved for (the host)
+    target: Option<String>,
+    /// sha256 of the Cargo.lock analyzed, to know what the report corresponds to
+    lockfile_sha256: Option<String>,
+}
 
 #[derive(Serialize, Deserialize)]
 struct JsonResult {
-    root_crates: HashSet<String>,
-    main_dependencies: HashSet<String>,
-    analysis_result: HashMap<String, analysis::PackageRisk>,
+    root_crates: BTreeSet<String>,
+    main_dependencies: BTreeSet<String>,
+    analysis_result: BTreeMap<String, analysis::PackageRisk>,
+    /// totals of the whole dependency tree (even if only the `--top` packages are listed)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    totals: Option<report::Totals>,
+    /// rules of the policy broken by the dependencies (see `--max-unsafe-loc`, etc.)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    policy_violations: Vec<policy::Violation>,
 }
 
-//
-// Main
-// ====
-//
+impl JsonResult {
+    /// totals of the whole dependency tree
+    /// (computed from the packages listed for reports that don't have them)
+    fn totals(&self) -> report::Totals {
+        self.totals
+            .clone()
+            .unwrap_or_else(|| report::Totals::new(&self.main_dependencies, &self.analysis_result))
+    }
+}
 
-fn main() {
-    // parse arguments
-    let matches = App::new("cargo-dephell")
+/// returns `$SOURCE_DATE_EPOCH` if set, the time to use in the outputs instead of the current one
+/// (so that two runs on the same lockfile give the same output)
+fn source_date_epoch() -> Option<chrono::DateTime<chrono::Utc>> {
+    use chrono::TimeZone;
+    std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.trim().parse().ok())
+        .and_then(|epoch| chrono::Utc.timestamp_opt(epoch, 0).single())
+}
+
+/// returns the target triple of the host (what `cargo check` builds for)
+fn host_target() -> Option<String> {
+    let output = std::process::Command::new("rustc")
+        .arg("-vV")
+        .output()
+        .ok()?;
+    let output = String::from_utf8(output.stdout).ok()?;
+    output
+        .lines()
+        .find(|line| line.starts_with("host: "))
+        .map(|line| line["host: ".len()..].trim().to_owned())
+}
+
+/// reads a previous JSON output (reports from before the envelope are still accepted)
+fn load_baseline(baseline_path: &str) -> Result<JsonResult, String> {
+    let baseline = std::fs::read_to_string(baseline_path).map_err(|err| err.to_string())?;
+    match serde_json::from_str::<JsonReport>(&baseline) {
+        Ok(report) => Ok(report.result),
+        Err(_) => serde_json::from_str(&baseline).map_err(|err| err.to_string()),
+    }
+}
+
+/// HistoryPoint is the totals of a previous report (`--history DIR`)
+#[derive(Serialize)]
+struct HistoryPoint {
+    /// when the analysis was run (RFC 3339)
+    timestamp: String,
+    totals: report::Totals,
+}
+
+/// loads the totals of the previous JSON outputs in a directory, oldest first
+/// (the outputs without metadata are dated by their modification time)
+fn load_history(history_dir: &str) -> Result<Vec<HistoryPoint>, String> {
+    let mut history = Vec::new();
+    for entry in std::fs::read_dir(history_dir).map_err(|err| err.to_string())? {
+        let path = entry.map_err(|err| err.to_string())?.path();
+        if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+            continue;
+        }
+        let contents = std::fs::read_to_string(&path).map_err(|err| err.to_string())?;
+        let point = match serde_json::from_str::<JsonReport>(&contents) {
+            Ok(report) => Some(HistoryPoint {
+                totals: report.result.totals(),
+                timestamp: report.metadata.timestamp,
+            }),
+            Err(_) => serde_json::from_str::<JsonResult>(&contents)
+                .ok()
+                .and_then(|result| {
+                    let modified = std::fs::metadata(&path)
+                        .and_then(|metadata| metadata.modified())
+                        .ok()?;
+                    Some(HistoryPoint {
+                        timestamp: chrono::DateTime::<chrono::Utc>::from(modified).to_rfc3339(),
+                        totals: result.totals(),
+                    })
+                }),
+        };
+        match point {
+            Some(point) => history.push(point),
+            None => eprintln!(
+                "dephell: skipping {}, it's not a JSON output of cargo dephell",
+                path.display()
+            ),
+        }
+    }
+    history.sort_by_key(|point| chrono::DateTime::parse_from_rfc3339(&point.timestamp).ok());
+    Ok(history)
+}
+
+/// validates the colors of the HTML output given on the command line
+fn validate_color(color: String) -> Result<(), String> {
+    if output::is_color(&color) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} is not a hex color (e.g. #be4141) or a color name",
+            color
+        ))
+    }
+}
+
+/// the command-line interface
+fn cli() -> App<'static, 'static> {
+    App::new("cargo-dephell")
         .version("1.0")
         .author("David W. <davidwg@fb.com>")
         .about("Risk management for third-party dependencies")
@@ -52,27 +176,247 @@ fn main() {
                 .takes_value(true)
                 .value_name("PATH"),
         )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .takes_value(true)
+                .value_name("PATH")
+                .help("defaults for the other arguments (by default, dephell.toml next to Cargo.toml if there's one)"),
+        )
         .arg(
             Arg::with_name("package")
                 .short("p")
+                .long("package")
                 .multiple(true)
                 .takes_value(true)
                 .value_name("PACKAGE")
                 .help("can be used to specify exactly which packages in a workspace to use"),
         )
         .arg(
-            Arg::with_name("html-output")
-                .help("prints the output as HTML (default JSON)")
+            Arg::with_name("output")
+                .help("writes the result to a file instead of stdout, can be used multiple times (the format is guessed from the extension if no FORMAT= or --format is given)")
                 .short("o")
+                .long("output")
+                .multiple(true)
+                .number_of_values(1)
+                .takes_value(true)
+                .value_name("[FORMAT=]PATH"),
+        )
+        .arg(
+            Arg::with_name("html-output")
+                .help("deprecated, use --output html=PATH")
                 .long("html-output")
-                .takes_value(true),
+                .takes_value(true)
+                .value_name("PATH")
+                .hidden(true),
+        )
+        .arg(
+            Arg::with_name("json-output")
+                .help("writes the JSON result to a file")
+                .long("json-output")
+                .takes_value(true)
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::with_name("pretty")
+                .long("pretty")
+                .help("pretty-prints the JSON result"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .help("format of the result, when printed or written with --output PATH (default json)")
+                .long("format")
+                .takes_value(true)
+                .possible_values(output::FORMATS),
+        )
+        .arg(
+            Arg::with_name("offline-html")
+                .long("offline-html")
+                .help("inlines the scripts and stylesheets in the HTML output instead of pulling them from CDNs (to view it offline)"),
+        )
+        .arg(
+            Arg::with_name("template")
+                .long("template")
+                .takes_value(true)
+                .value_name("PATH")
+                .help("renders the result with a Tera template (it gets the JSON report, and the name of the project)"),
+        )
+        .arg(
+            Arg::with_name("printable")
+                .long("printable")
+                .help("outputs HTML for printing (no javascript, a page per section)"),
+        )
+        .arg(
+            Arg::with_name("theme")
+                .long("theme")
+                .takes_value(true)
+                .possible_values(output::THEMES)
+                .default_value("auto")
+                .help("default theme of the HTML output (it can be switched on the page)"),
+        )
+        .arg(
+            Arg::with_name("brand-color")
+                .long("brand-color")
+                .takes_value(true)
+                .value_name("COLOR")
+                .default_value("#be4141")
+                .validator(validate_color)
+                .help("color of the header of the HTML output"),
+        )
+        .arg(
+            Arg::with_name("accent-color")
+                .long("accent-color")
+                .takes_value(true)
+                .value_name("COLOR")
+                .default_value("#6c7ae0")
+                .validator(validate_color)
+                .help("color of the table headers of the HTML output"),
+        )
+        .arg(
+            Arg::with_name("columns")
+                .long("columns")
+                .takes_value(true)
+                .value_name("COLUMNS")
+                .use_delimiter(true)
+                .possible_values(output::COLUMNS)
+                .help("comma-separated columns shown by default in the HTML output (they can be changed on the page)"),
+        )
+        .arg(
+            Arg::with_name("grade-thresholds")
+                .long("grade-thresholds")
+                .takes_value(true)
+                .value_name("B,C,D,F")
+                .default_value("10,25,50,80")
+                .validator(|value| output::parse_grade_thresholds(&value).map(|_| ()))
+                .help("risk scores from which a dependency is graded B, C, D and F in the HTML output"),
+        )
+        .arg(
+            Arg::with_name("notices")
+                .long("notices")
+                .takes_value(true)
+                .value_name("PATH")
+                .help("writes the licenses and license texts of the dependencies (THIRD-PARTY-NOTICES)"),
+        )
+        .arg(
+            Arg::with_name("site")
+                .long("site")
+                .takes_value(true)
+                .value_name("DIR")
+                .help("writes a static site with a page per dependency (e.g. for github pages)"),
+        )
+        .arg(
+            Arg::with_name("site-sources")
+                .long("site-sources")
+                .requires("site")
+                .help("also writes the source files with unsafe code, FFI, network access or obfuscated code in the site, with the flagged lines highlighted"),
+        )
+        .arg(
+            Arg::with_name("top")
+                .long("top")
+                .takes_value(true)
+                .value_name("N")
+                .validator(|top| top.parse::<usize>().map(|_| ()).map_err(|err| err.to_string()))
+                .help("only outputs the N riskiest dependencies (and the totals of the whole tree)"),
+        )
+        .arg(
+            Arg::with_name("badge-metric")
+                .long("badge-metric")
+                .takes_value(true)
+                .possible_values(report::BADGE_METRICS)
+                .default_value("dependencies")
+                .help("what the badge and shields formats show"),
+        )
+        .arg(Arg::with_name("github-actions").long("github-actions").help(
+            "writes findings as github actions annotations, and a summary to $GITHUB_STEP_SUMMARY",
+        ))
+        .arg(
+            Arg::with_name("baseline")
+                .long("baseline")
+                .visible_alias("compare-to")
+                .takes_value(true)
+                .value_name("PATH")
+                .help("a previous JSON output of cargo dephell, to show what changed since (in the markdown and HTML outputs)"),
+        )
+        .arg(
+            Arg::with_name("history")
+                .long("history")
+                .takes_value(true)
+                .value_name("DIR")
+                .help("a directory of previous JSON outputs of cargo dephell, to chart the trends in the HTML output"),
+        )
+        .arg(
+            Arg::with_name("max-unsafe-loc")
+                .long("max-unsafe-loc")
+                .takes_value(true)
+                .value_name("N")
+                .validator(|max| max.parse::<u64>().map(|_| ()).map_err(|err| err.to_string()))
+                .help("policy: fails if a dependency has more than N lines of unsafe code"),
+        )
+        .arg(
+            Arg::with_name("max-new-dependencies")
+                .long("max-new-dependencies")
+                .takes_value(true)
+                .value_name("N")
+                .validator(|max| max.parse::<u64>().map(|_| ()).map_err(|err| err.to_string()))
+                .help("policy: fails if more than N third-party dependencies were added since the --baseline"),
+        )
+        .arg(
+            Arg::with_name("min-stars")
+                .long("min-stars")
+                .takes_value(true)
+                .value_name("N")
+                .validator(|min| min.parse::<u64>().map(|_| ()).map_err(|err| err.to_string()))
+                .help("policy: fails if the repository of a dependency has less than N stars"),
+        )
+        .arg(
+            Arg::with_name("no-advisories")
+                .long("no-advisories")
+                .help("policy: fails if a dependency is affected by a known advisory"),
+        )
+        .arg(
+            Arg::with_name("no-yanked")
+                .long("no-yanked")
+                .help("policy: fails if a yanked version is pulled"),
+        )
+        .arg(
+            Arg::with_name("only-regressions")
+                .long("only-regressions")
+                .help("policy: only fails on the violations that are new or worse than in the --baseline"),
         )
         .arg(
             Arg::with_name("github-token")
                 .long("github-token")
                 .takes_value(true)
-                .value_name("USER:TOKEN")
-                .help("allows the CLI to retrieve github repos stats"),
+                .value_name("[USER:]TOKEN")
+                .help("allows the CLI to retrieve github repos stats (or set $GITHUB_TOKEN)"),
+        )
+        .arg(
+            Arg::with_name("github-token-env")
+                .long("github-token-env")
+                .takes_value(true)
+                .value_name("VAR")
+                .help("the environment variable holding the github token (by default $DEPHELL_GITHUB_TOKEN or $GITHUB_TOKEN)"),
+        )
+        .arg(
+            Arg::with_name("github-api-url")
+                .long("github-api-url")
+                .takes_value(true)
+                .value_name("URL")
+                .help("base URL of the github API, to use a GitHub Enterprise instance"),
+        )
+        .arg(
+            Arg::with_name("gitlab-host")
+                .long("gitlab-host")
+                .takes_value(true)
+                .value_name("HOST")
+                .help("a self-hosted gitlab instance to retrieve repos stats from"),
+        )
+        .arg(
+            Arg::with_name("librariesio-key")
+                .long("librariesio-key")
+                .takes_value(true)
+                .value_name("KEY")
+                .help("a libraries.io API key, to retrieve the SourceRank of dependencies"),
         )
         .arg(
             Arg::with_name("proxy")
@@ -81,9 +425,44 @@ fn main() {
                 .value_name("PROTOCOL://IP:PORT")
                 .help("uses a proxy to make external requests to github"),
         )
+        .arg(
+            Arg::with_name("no-cache").long("no-cache").help(
+                "don't cache API responses on disk (they are revalidated with their ETags)",
+            ),
+        )
+        .arg(
+            Arg::with_name("osv")
+                .long("osv")
+                .help("also queries OSV.dev for vulnerabilities affecting dependencies"),
+        )
+        .arg(
+            Arg::with_name("vet-audits")
+                .long("vet-audits")
+                .takes_value(true)
+                .value_name("PATH")
+                .help("marks dependencies audited in a cargo-vet audits.toml as vetted (whatever the criteria of the audits)"),
+        )
+        .arg(
+            Arg::with_name("notes")
+                .long("notes")
+                .takes_value(true)
+                .value_name("PATH")
+                .help("reviewer notes about the dependencies (by default, dephell-notes.toml next to Cargo.toml if there's one)"),
+        )
+        .arg(
+            Arg::with_name("review-state")
+                .long("review-state")
+                .takes_value(true)
+                .value_name("PATH")
+                .help("the versions of the crates that were signed off, to show the ones that need to be reviewed"),
+        )
+        .arg(Arg::with_name("verify-source").long("verify-source").help(
+            "diffs the code published on crates.io with the code on the repository, and looks for the tags of the versions (slow)",
+        ))
         .arg(
             Arg::with_name("ignore-workspace")
                 .short("i")
+                .long("ignore-workspace")
                 .multiple(true)
                 .takes_value(true)
                 .value_name("CRATE_NAME")
@@ -97,7 +476,59 @@ fn main() {
         )
         // cargo install cargo-dephell won't work without this
         .arg(Arg::with_name("catch-cargo-cli-bug"))
-        .get_matches();
+        .subcommand(
+            SubCommand::with_name("serve")
+                .about("runs the analysis and serves the HTML report (and the JSON on /api/report) on localhost")
+                .arg(
+                    Arg::with_name("port")
+                        .long("port")
+                        .takes_value(true)
+                        .value_name("PORT")
+                        .default_value("8080")
+                        .validator(|port| port.parse::<u16>().map(|_| ()).map_err(|err| err.to_string())),
+                ),
+        )
+}
+
+//
+// Main
+// ====
+//
+
+/// exit code when the policy is not respected
+const EXIT_POLICY_VIOLATIONS: i32 = 1;
+
+/// exit code when the analysis, or writing an output, failed
+const EXIT_ERROR: i32 = 2;
+
+fn main() {
+    // parse arguments (the ones not given default to the ones in dephell.toml, if there's one)
+    let mut args: Vec<std::ffi::OsString> = std::env::args_os().collect();
+    let config = config::find_config(&args);
+    if let Some(config) = &config {
+        args = match config::merge_args(config, args) {
+            Ok(args) => args,
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(EXIT_ERROR);
+            }
+        };
+    }
+    let matches = match (cli().get_matches_from_safe(args), &config) {
+        (Ok(matches), _) => matches,
+        (Err(err), Some(config))
+            if err.kind != clap::ErrorKind::HelpDisplayed
+                && err.kind != clap::ErrorKind::VersionDisplayed =>
+        {
+            eprintln!(
+                "{}\n(with the arguments of {})",
+                err.message,
+                config.path.display()
+            );
+            std::process::exit(EXIT_ERROR);
+        }
+        (Err(err), _) => err.exit(),
+    };
 
     // get metadata from manifest path
     let manifest_path = matches
@@ -109,8 +540,57 @@ fn main() {
             current_dir.to_str().unwrap().to_owned()
         });
 
-    // quiet if wanted, or JSON
-    let quiet = matches.is_present("quiet") || !matches.is_present("html-output");
+    // where and how to output the result
+    if matches.is_present("html-output") {
+        eprintln!("dephell: --html-output is deprecated, use --output html=PATH");
+    }
+    // (the result is rendered with the template if there's one)
+    let format = matches
+        .value_of("format")
+        .and_then(output::Format::from_name)
+        .or_else(|| {
+            matches
+                .value_of("template")
+                .map(|_| output::Format::Template)
+        });
+    let mut outputs: Vec<(output::Format, Option<&str>)> = matches
+        .values_of("output")
+        .into_iter()
+        .flatten()
+        .map(|value| {
+            let (output_format, output_path) = output::parse_output(value);
+            let output_format = output_format
+                .or(format)
+                .or_else(|| output::Format::from_extension(output_path))
+                .unwrap_or(output::Format::Json);
+            (output_format, Some(output_path))
+        })
+        .collect();
+    if let Some(html_output) = matches.value_of("html-output") {
+        outputs.push((output::Format::Html, Some(html_output)));
+    }
+    let to_files =
+        !outputs.is_empty() || matches.is_present("site") || matches.is_present("notices");
+
+    // the result goes to stdout if it's not written to a file,
+    // unless only other outputs (--json-output, --github-actions) were asked for
+    // (a table for humans if stdout is a terminal, JSON otherwise)
+    let terminal = atty::is(atty::Stream::Stdout);
+    if !to_files
+        && (format.is_some()
+            || !(matches.is_present("json-output") || matches.is_present("github-actions")))
+    {
+        let default_format = if terminal {
+            output::Format::Table
+        } else {
+            output::Format::Json
+        };
+        outputs.push((format.unwrap_or(default_format), None));
+    }
+
+    // quiet if wanted, or if the result is printed to stdout
+    let quiet = matches.is_present("quiet")
+        || (!to_files && (format.is_some() || !matches.is_present("json-output")));
 
     // pretty hello world :>
     if !quiet {
@@ -120,16 +600,28 @@ fn main() {
         println!("  please wait, this can take a while...\n");
     }
 
-    // parse github token (if given)
-    let github_token = matches.value_of("github-token").and_then(|github_token| {
-        let github_token: Vec<&str> = github_token.split(":").collect();
-        if github_token.len() != 2 {
-            eprintln!("wrong github-token, must be of the form username:token");
-            return None;
+    // parse github token (if given), the `username:` prefix is optional
+    let env_github_token = match matches.value_of("github-token-env") {
+        Some(var) => std::env::var(var),
+        None => std::env::var("DEPHELL_GITHUB_TOKEN").or_else(|_| std::env::var("GITHUB_TOKEN")),
+    }
+    .ok();
+    let github_token = matches
+        .value_of("github-token")
+        .or_else(|| env_github_token.as_deref())
+        .map(|github_token| match github_token.find(':') {
+            Some(idx) => &github_token[idx + 1..],
+            None => github_token,
+        })
+        .filter(|github_token| !github_token.is_empty());
+
+    // parse gitlab host (if given)
+    let gitlab_host = matches.value_of("gitlab-host").map(|gitlab_host| {
+        let gitlab_host = gitlab_host.trim_end_matches('/');
+        match gitlab_host.find("://") {
+            Some(idx) => &gitlab_host[idx + 3..],
+            None => gitlab_host,
         }
-        let username = github_token[0];
-        let token = github_token[1];
-        Some((username, token))
     });
 
     // create an HTTP client (used for example to query github API to get # of stars)
@@ -139,12 +631,18 @@ fn main() {
             Ok(x) => x,
             Err(err) => {
                 eprintln!("{}", err);
-                return;
+                std::process::exit(EXIT_ERROR);
             }
         };
         http_client = http_client.proxy(reqwest_proxy);
     }
     let http_client = http_client.build().unwrap();
+    let cache_dir = if matches.is_present("no-cache") {
+        None
+    } else {
+        http::default_cache_dir()
+    };
+    let http_client = http::HttpClient::new(http_client, cache_dir);
 
     // parse dependencies to ignore
     let to_ignore = matches.values_of("ignore-workspace");
@@ -154,63 +652,396 @@ fn main() {
     let packages = matches.values_of("package");
     let packages: Option<Vec<&str>> = packages.map(|x| x.collect());
 
+    // load the baseline (if given)
+    let baseline: Option<JsonResult> = match matches.value_of("baseline") {
+        None => None,
+        Some(baseline_path) => match load_baseline(baseline_path) {
+            Ok(baseline) => Some(baseline),
+            Err(err) => {
+                eprintln!(
+                    "dephell: couldn't read the baseline {}: {}",
+                    baseline_path, err
+                );
+                std::process::exit(EXIT_ERROR);
+            }
+        },
+    };
+
+    // load the reviewer notes (if given, or next to Cargo.toml)
+    let notes_path = match matches.value_of("notes") {
+        Some(notes_path) => Some(PathBuf::from(notes_path)),
+        None => std::path::Path::new(&manifest_path)
+            .parent()
+            .map(|manifest_dir| manifest_dir.join(notes::NOTES_FILE))
+            .filter(|notes_path| notes_path.exists()),
+    };
+    let reviewer_notes = match notes_path {
+        None => None,
+        Some(notes_path) => match notes::parse_notes(&notes_path) {
+            Ok(reviewer_notes) => Some(reviewer_notes),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(EXIT_ERROR);
+            }
+        },
+    };
+
+    // load the previous outputs (if given)
+    let history = match matches.value_of("history") {
+        None => Vec::new(),
+        Some(history_dir) => match load_history(history_dir) {
+            Ok(history) => history,
+            Err(err) => {
+                eprintln!(
+                    "dephell: couldn't read the history {}: {}",
+                    history_dir, err
+                );
+                std::process::exit(EXIT_ERROR);
+            }
+        },
+    };
+
+    // inline the scripts and stylesheets of the HTML output (if it must work offline)
+    let html_assets = if matches.is_present("offline-html") {
+        Some(output::html_assets())
+    } else {
+        None
+    };
+
+    // load the sign-offs (if given)
+    let sign_offs = match matches.value_of("review-state") {
+        None => None,
+        Some(review_state_path) => match review::parse_review_state(review_state_path) {
+            Ok(sign_offs) => Some(sign_offs),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(EXIT_ERROR);
+            }
+        },
+    };
+
+    // JSON lines are streamed during the analysis
+    let mut jsonl_outputs = Vec::new();
+    for (output_format, output_path) in &outputs {
+        if *output_format == output::Format::Jsonl {
+            match output::create(*output_path) {
+                Ok(jsonl_output) => jsonl_outputs.push(jsonl_output),
+                Err(err) => {
+                    eprintln!("dephell: couldn't write the jsonl output: {}", err);
+                    std::process::exit(EXIT_ERROR);
+                }
+            }
+        }
+    }
+    let jsonl_outputs = std::cell::RefCell::new(jsonl_outputs);
+    let print_package = |package_id: &str, package_risk: &analysis::PackageRisk| {
+        let line = serde_json::json!({ "id": package_id, "package": package_risk });
+        for jsonl_output in jsonl_outputs.borrow_mut().iter_mut() {
+            if let Err(err) = writeln!(jsonl_output, "{}", line) {
+                eprintln!("dephell: couldn't write the jsonl output: {}", err);
+            }
+        }
+    };
+    let stream_jsonl = !jsonl_outputs.borrow().is_empty();
+
     // do the analysis
-    let result = analysis::analyze_repo(
-        &manifest_path,
-        http_client,
+    let start_time = chrono::Utc::now();
+    let options = analysis::AnalysisOptions {
         github_token,
+        github_api_url: matches.value_of("github-api-url"),
+        gitlab_host,
+        librariesio_key: matches.value_of("librariesio-key"),
         packages,
         to_ignore,
+        osv: matches.is_present("osv"),
+        vet_audits: matches.value_of("vet-audits"),
+        verify_source: matches.is_present("verify-source"),
         quiet,
-    );
-    let (root_crates, main_dependencies, analysis_result) = match result {
+        on_package: if stream_jsonl {
+            Some(&print_package)
+        } else {
+            None
+        },
+    };
+    let result = analysis::analyze_repo(&manifest_path, http_client, &options);
+    let (root_crates, mut main_dependencies, mut analysis_result) = match result {
         Err(err) => {
             eprintln!("{}", err);
-            return;
+            std::process::exit(EXIT_ERROR);
         }
         Ok(x) => x,
     };
 
+    // add the reviewer notes
+    if let Some(reviewer_notes) = &reviewer_notes {
+        for package_risk in analysis_result.values_mut() {
+            package_risk.reviewer_notes = reviewer_notes.get(&package_risk.name).cloned();
+        }
+    }
+
+    // check which third-party dependencies have been reviewed
+    if let Some(sign_offs) = &sign_offs {
+        for package_risk in analysis_result.values_mut() {
+            if !package_risk.internal {
+                package_risk.review = Some(review::get_review(
+                    sign_offs,
+                    &package_risk.name,
+                    &package_risk.versions,
+                ));
+            }
+        }
+    }
+
+    // name of the project (the directory containing the manifest)
+    let name = std::path::Path::new(&manifest_path)
+        .parent()
+        .unwrap()
+        .file_name()
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_owned();
+
+    // the notices are about every dependency, even with --top
+    let notices = matches
+        .value_of("notices")
+        .map(|_| report::to_notices(&name, &analysis_result));
+
+    // check the policy on the whole tree
+    let policy = policy::Policy {
+        max_unsafe_loc: matches
+            .value_of("max-unsafe-loc")
+            .map(|max| max.parse().unwrap()),
+        max_new_dependencies: matches
+            .value_of("max-new-dependencies")
+            .map(|max| max.parse().unwrap()),
+        min_stars: matches
+            .value_of("min-stars")
+            .map(|min| min.parse().unwrap()),
+        no_advisories: matches.is_present("no-advisories"),
+        no_yanked: matches.is_present("no-yanked"),
+        only_regressions: matches.is_present("only-regressions"),
+    };
+    if policy.max_new_dependencies.is_some() && baseline.is_none() {
+        eprintln!("dephell: --max-new-dependencies is ignored without a --baseline to compare to");
+    }
+    if policy.only_regressions && baseline.is_none() {
+        eprintln!("dephell: --only-regressions is ignored without a --baseline to compare to");
+    }
+    let policy_violations = policy.check(
+        &analysis_result,
+        baseline.as_ref().map(|baseline| &baseline.analysis_result),
+    );
+
+    // keep only the riskiest dependencies if asked (totals are about the whole tree)
+    let totals = report::Totals::new(&main_dependencies, &analysis_result);
+    if let Some(top) = matches.value_of("top") {
+        let top = top.parse().unwrap();
+        report::keep_top(top, &mut main_dependencies, &mut analysis_result);
+    }
+
     // convert result to JSON
-    let json_result = JsonResult {
-        root_crates,
+    let lockfile_sha256 =
+        report::find_lockfile(std::path::Path::new(&manifest_path)).map(|lockfile| {
+            use sha2::{Digest, Sha256};
+            format!("{:x}", Sha256::digest(lockfile.as_bytes()))
+        });
+    let json_report = JsonReport {
+        schema_version: SCHEMA_VERSION,
+        metadata: RunMetadata {
+            tool_version: env!("CARGO_PKG_VERSION").to_owned(),
+            timestamp: source_date_epoch().unwrap_or(start_time).to_rfc3339(),
+            target: host_target(),
+            lockfile_sha256,
+        },
+        result: JsonResult {
+            root_crates,
+            main_dependencies,
+            analysis_result,
+            totals: Some(totals),
+            policy_violations,
+        },
+    };
+    let columns: Option<Vec<&str>> = matches
+        .values_of("columns")
+        .map(|columns| columns.collect());
+    let context = output::Context {
+        name: &name,
+        manifest_path: std::path::Path::new(&manifest_path),
+        report: &json_report,
+        baseline: baseline.as_ref(),
+        history: &history,
+        start_time: source_date_epoch().unwrap_or(start_time),
+        end_time: source_date_epoch().unwrap_or_else(chrono::Utc::now),
+        pretty: matches.is_present("pretty"),
+        badge_metric: report::BadgeMetric::from_name(matches.value_of("badge-metric").unwrap())
+            .unwrap(),
+        color: false,
+        printable: matches.is_present("printable"),
+        theme: output::Theme {
+            default: matches.value_of("theme").unwrap(),
+            brand_color: matches.value_of("brand-color").unwrap(),
+            accent_color: matches.value_of("accent-color").unwrap(),
+        },
+        columns: columns.as_deref(),
+        grade_thresholds: output::parse_grade_thresholds(
+            matches.value_of("grade-thresholds").unwrap(),
+        )
+        .unwrap(),
+        template: matches.value_of("template").map(std::path::Path::new),
+        html_assets: html_assets.as_ref(),
+    };
+
+    // serve the report instead of writing it
+    if let Some(serve_matches) = matches.subcommand_matches("serve") {
+        let port = serve_matches.value_of("port").unwrap().parse().unwrap();
+        if let Err(err) = serve::serve(port, &context) {
+            eprintln!("dephell: couldn't serve the report: {}", err);
+            std::process::exit(EXIT_ERROR);
+        }
+        return;
+    }
+
+    let JsonResult {
         main_dependencies,
         analysis_result,
-    };
-    let json_result = serde_json::to_string(&json_result).unwrap();
-
-    // print out result
-    use std::fs::File;
-    use std::io::prelude::*;
-    match matches.value_of("html-output") {
-        None => {
-            println!("{}", json_result);
-        }
-        Some(html_output) => {
-            let name = std::path::Path::new(&manifest_path)
-                .parent()
-                .unwrap()
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_owned();
-            let html_page = HtmlList {
-                name: name,
-                json_result: base64::encode(json_result),
-            };
-            let mut file = match File::create(html_output) {
-                Ok(x) => x,
-                Err(err) => {
-                    eprintln!("{}", err);
-                    return;
-                }
-            };
-            let _ = write!(&mut file, "{}", html_page.render().unwrap()).unwrap();
-            if !quiet {
-                println!("\n=> html output saved at {}", html_output);
+        ..
+    } = &json_report.result;
+    let totals = json_report.result.totals();
+
+    // github actions annotations (on stderr, as the outputs can be on stdout) and job summary
+    if matches.is_present("github-actions") {
+        let findings = findings::get_findings(main_dependencies, analysis_result);
+        eprint!(
+            "{}",
+            report::to_github_annotations(
+                std::path::Path::new(&manifest_path),
+                &findings,
+                &json_report.result.policy_violations,
+            )
+        );
+        if let Some(step_summary) = std::env::var_os("GITHUB_STEP_SUMMARY") {
+            let baseline = baseline
+                .as_ref()
+                .map(|baseline| (baseline.totals(), &baseline.analysis_result));
+            let summary = report::to_markdown(
+                &name,
+                &totals,
+                analysis_result,
+                &findings,
+                &json_report.result.policy_violations,
+                baseline.as_ref().map(|(totals, result)| (totals, *result)),
+            );
+            let appended = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&step_summary)
+                .and_then(|mut file| writeln!(file, "{}", summary));
+            if let Err(err) = appended {
+                eprintln!("dephell: couldn't write the job summary: {}", err);
             }
         }
-    };
-    //
+    }
+
+    // write out the results (JSON lines go where the packages were streamed)
+    // (a failure to write an output doesn't prevent the others, nor the policy check)
+    let mut failed = false;
+    let mut jsonl_outputs = jsonl_outputs.into_inner().into_iter();
+    for (output_format, output_path) in outputs {
+        // only what's printed to a terminal is colored
+        let context = output::Context {
+            color: terminal && output_path.is_none() && std::env::var_os("NO_COLOR").is_none(),
+            ..context
+        };
+        let rendered = match output::render(output_format, &context) {
+            Ok(rendered) => rendered,
+            Err(err) => {
+                eprintln!(
+                    "dephell: couldn't render the {} output: {}",
+                    output_format.name(),
+                    err
+                );
+                failed = true;
+                continue;
+            }
+        };
+        let out = match output_format {
+            output::Format::Jsonl => Ok(jsonl_outputs.next().unwrap()),
+            _ => output::create(output_path),
+        };
+        if let Err(err) = out.and_then(|mut out| writeln!(out, "{}", rendered)) {
+            eprintln!(
+                "dephell: couldn't write the {} output: {}",
+                output_format.name(),
+                err
+            );
+            failed = true;
+            continue;
+        }
+        if let (Some(output_path), false) = (output_path, quiet) {
+            println!(
+                "\n=> {} output saved at {}",
+                output_format.name(),
+                output_path
+            );
+        }
+    }
+    if let Some(site_dir) = matches.value_of("site") {
+        let site_path = std::path::Path::new(site_dir);
+        let with_sources = matches.is_present("site-sources");
+        if let Err(err) = output::write_site(site_path, &context, with_sources) {
+            eprintln!("dephell: couldn't write the site: {}", err);
+            failed = true;
+        } else if !quiet {
+            println!("\n=> site saved in {}", site_dir);
+        }
+    }
+    if let (Some(notices_path), Some(notices)) = (matches.value_of("notices"), notices) {
+        if let Err(err) = std::fs::write(notices_path, notices) {
+            eprintln!("dephell: couldn't write {}: {}", notices_path, err);
+            failed = true;
+        } else if !quiet {
+            println!("\n=> notices saved at {}", notices_path);
+        }
+    }
+    if let Some(json_path) = matches.value_of("json-output") {
+        let json_output =
+            output::render(output::Format::Json, &context).expect("JSON can always be rendered");
+        if let Err(err) = std::fs::write(json_path, &json_output) {
+            eprintln!("dephell: couldn't write {}: {}", json_path, err);
+            failed = true;
+        } else if !quiet {
+            println!("\n=> json output saved at {}", json_path);
+        }
+    }
+
+    // fail if the policy is not respected (violations already in the baseline are only reported)
+    let policy_violations = &json_report.result.policy_violations;
+    if !policy_violations.is_empty() {
+        eprintln!(
+            "\ndephell: {} policy violation(s):",
+            policy_violations.len()
+        );
+        for violation in policy_violations {
+            let in_baseline = if violation.in_baseline {
+                " (already in the baseline)"
+            } else {
+                ""
+            };
+            eprintln!(
+                "  [{}] {}{}",
+                violation.rule, violation.message, in_baseline
+            );
+        }
+    }
+    let violated = policy_violations
+        .iter()
+        .any(|violation| !violation.in_baseline);
+
+    std::io::stdout().flush().ok();
+    if failed {
+        std::process::exit(EXIT_ERROR);
+    } else if violated {
+        std::process::exit(EXIT_POLICY_VIOLATIONS);
+    }
 }
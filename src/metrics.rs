@@ -1,101 +1,1555 @@
 use guppy::graph::{DependencyDirection, PackageGraph, PackageMetadata};
 use guppy::PackageId;
 use serde::Deserialize;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use crate::analysis::PackageRisk;
+use crate::analysis::{package_key, Advisory, PackageRisk, UnsafeLocation, UnsafeUsage};
+use crate::http::HttpClient;
+use crate::scan::{self, RustFile};
+use syn::visit::{self, Visit};
 
 //
 // Analysis Functions
 // ==================
 //
 
-/// get number of stars a repo has
-pub fn get_github_stars(
-    http_client: reqwest::blocking::Client,
-    github_token: (&str, &str),
+/// RepoUrl is a normalized repository URL
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepoUrl {
+    /// the host (e.g. github.com)
+    pub host: String,
+    /// the owner of the repository (can contain several levels of groups on gitlab)
+    pub owner: String,
+    /// the name of the repository
+    pub name: String,
+    /// the sub-directory of the repository pointed to, if any (for monorepos)
+    pub sub_path: Option<String>,
+}
+
+impl RepoUrl {
+    /// returns "owner/name"
+    pub fn full_name(&self) -> String {
+        format!("{}/{}", self.owner, self.name)
+    }
+
+    /// returns an https URL that can be cloned
+    pub fn url(&self) -> String {
+        format!("https://{}/{}", self.host, self.full_name())
+    }
+}
+
+/// hosts where repositories are always of the form `owner/name`
+const TWO_LEVEL_HOSTS: &[&str] = &[
+    "github.com",
+    "bitbucket.org",
+    "codeberg.org",
+    "gitea.com",
+    "git.sr.ht",
+];
+
+/// path segments that separate the repository from a path inside it
+/// (`/tree/<branch>/...` on github, `/-/tree/<branch>/...` on gitlab,
+/// `/src/branch/<branch>/...` on gitea, `/tree/<branch>/item/...` on sourcehut)
+const REPO_PATH_MARKERS: &[&str] = &["-", "tree", "blob", "src"];
+
+/// extracts the host, owner, name and sub-path from the many forms a repository URL can take:
+/// `.git` suffixes, `git+https://` and `git@host:owner/name` forms, links to sub-directories, etc.
+pub fn normalize_repo_url(repo_url: &str) -> Option<RepoUrl> {
+    // get rid of the scheme, the query string and the fragment
+    let repo_url = repo_url.trim();
+    let repo_url = repo_url.split(|c| c == '?' || c == '#').next()?;
+    let without_scheme = match repo_url.find("://") {
+        Some(idx) => repo_url[idx + 3..].to_owned(),
+        // scp-like syntax (git@github.com:owner/name.git)
+        None => repo_url.replacen(':', "/", 1),
+    };
+
+    // split host and path
+    let mut segments = without_scheme
+        .split('/')
+        .filter(|segment| !segment.is_empty());
+    let host = segments.next()?;
+    let host = host.rsplit('@').next()?; // user info
+    let host = host.split(':').next()?; // port
+    let host = host.to_lowercase();
+    let host = host.trim_start_matches("www.").to_owned();
+    let segments: Vec<&str> = segments.collect();
+
+    // find where the repository ends
+    let repo_len = if TWO_LEVEL_HOSTS.contains(&host.as_str()) {
+        2
+    } else {
+        segments
+            .iter()
+            .position(|segment| REPO_PATH_MARKERS.contains(segment))
+            .unwrap_or_else(|| segments.len())
+    };
+    if repo_len < 2 || segments.len() < repo_len {
+        return None;
+    }
+    let owner = segments[..repo_len - 1].join("/");
+    let name = segments[repo_len - 1].trim_end_matches(".git").to_owned();
+
+    // find the sub-path (skipping the markers and the branch)
+    let mut rest = &segments[repo_len..];
+    if rest.first() == Some(&"-") {
+        rest = &rest[1..];
+    }
+    let sub_path = if rest.len() > 2 && REPO_PATH_MARKERS.contains(&rest[0]) {
+        let mut path = &rest[2..];
+        // gitea: src/branch/<branch>/... | sourcehut: tree/<branch>/item/...
+        if rest[1] == "branch" || rest[1] == "commit" || rest[1] == "tag" {
+            path = &path[1..];
+        } else if path.first() == Some(&"item") {
+            path = &path[1..];
+        }
+        if path.is_empty() {
+            None
+        } else {
+            Some(path.join("/"))
+        }
+    } else {
+        None
+    };
+
+    Some(RepoUrl {
+        host,
+        owner,
+        name,
+        sub_path,
+    })
+}
+
+/// RepoStats contains statistics about a repository, whatever its host
+#[derive(Debug, Default)]
+pub struct RepoStats {
+    /// number of stars (or watchers on bitbucket), if the host has such a thing
+    pub stars: Option<u64>,
+    /// date of the last commit on the default branch
+    pub last_commit: Option<String>,
+    /// has the repository been archived (read-only)?
+    pub archived: bool,
+    /// if the repository is a fork, the repository it was forked from
+    pub fork_of: Option<String>,
+}
+
+/// formats an RFC 3339 date as YYYY-MM-DD
+fn format_date(date: &str) -> Option<String> {
+    chrono::DateTime::parse_from_rfc3339(date)
+        .ok()
+        .map(|date| date.format("%Y-%m-%d").to_string())
+}
+
+/// the public github API
+pub const GITHUB_API_URL: &str = "https://api.github.com";
+
+/// GithubApi is the github API to query: github.com, or a GitHub Enterprise instance
+#[derive(Debug, Clone, Copy)]
+pub struct GithubApi<'a> {
+    /// a personal access token, or any other github token (e.g. from github actions)
+    pub token: &'a str,
+    /// base URL of the REST API (e.g. https://github.example.com/api/v3)
+    pub api_url: &'a str,
+}
+
+impl<'a> GithubApi<'a> {
+    /// returns the URL of a REST API endpoint
+    fn rest_url(&self, path: &str) -> String {
+        format!("{}/{}", self.api_url.trim_end_matches('/'), path)
+    }
+
+    /// returns the URL of the GraphQL API
+    /// (GitHub Enterprise serves REST at /api/v3 and GraphQL at /api/graphql)
+    fn graphql_url(&self) -> String {
+        let api_url = self.api_url.trim_end_matches('/');
+        let api_url = if api_url.ends_with("/v3") {
+            &api_url[..api_url.len() - "/v3".len()]
+        } else {
+            api_url
+        };
+        format!("{}/graphql", api_url)
+    }
+
+    /// returns true if the repository can be queried through this API:
+    /// github.com repositories (the API might be a proxy),
+    /// or repositories of the GitHub Enterprise instance
+    pub fn hosts(&self, repo_url: &RepoUrl) -> bool {
+        if repo_url.host == "github.com" {
+            return true;
+        }
+        let without_scheme = self.api_url.splitn(2, "://").last().unwrap_or_default();
+        let api_host = without_scheme.split('/').next().unwrap_or_default();
+        api_host.to_lowercase().trim_start_matches("api.") == repo_url.host
+    }
+}
+
+/// sends an authenticated GET request to the github API and parses the JSON response
+fn github_get<T: serde::de::DeserializeOwned>(
+    http_client: HttpClient,
+    github: &GithubApi,
+    request_url: &str,
+) -> Option<T> {
+    let mut request = http_client.get(request_url);
+
+    // use the github token
+    request = request.bearer_auth(github.token);
+
+    // send the request
+    let resp = match http_client.send(request) {
+        Err(err) => {
+            eprintln!("{}", err);
+            return None;
+        }
+        Ok(resp) => resp,
+    };
+
+    if !resp.status.is_success() {
+        eprintln!("dephell: github request failed");
+        eprintln!("query: {}", request_url);
+        eprintln!("status: {}", resp.status);
+        eprintln!("text: {:?}", resp.body);
+        return None;
+    }
+    let resp: serde_json::Result<T> = resp.json();
+    match resp {
+        Ok(x) => Some(x),
+        Err(err) => {
+            eprintln!("dephell: {}", err);
+            None
+        }
+    }
+}
+
+/// get information about a github repo (stars, last commit, etc.)
+pub fn get_github_repo(
+    http_client: HttpClient,
+    github: &GithubApi,
     repo: &str,
-) -> Option<u64> {
+) -> Option<RepoStats> {
     #[derive(Deserialize, Debug)]
-    pub struct GithubResponse {
-        #[serde(rename = "stargazers_count")]
+    pub struct Parent {
+        full_name: String,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct GithubRepo {
         stargazers_count: u64,
+        default_branch: String,
+        archived: bool,
+        fork: bool,
+        parent: Option<Parent>,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct Committer {
+        date: String,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct Commit {
+        committer: Committer,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct CommitInfo {
+        commit: Commit,
+    }
+
+    // repo information
+    let repo = repo.trim_end_matches(".git");
+    let request_url = github.rest_url(&format!("repos/{}", repo));
+    let github_repo: GithubRepo = github_get(http_client.clone(), github, &request_url)?;
+
+    // last commit on the default branch
+    let request_url = github.rest_url(&format!(
+        "repos/{}/commits/{}",
+        repo, github_repo.default_branch
+    ));
+    let commit_info: Option<CommitInfo> = github_get(http_client, github, &request_url);
+
+    Some(RepoStats {
+        stars: Some(github_repo.stargazers_count),
+        last_commit: commit_info.and_then(|info| format_date(&info.commit.committer.date)),
+        archived: github_repo.archived,
+        fork_of: if github_repo.fork {
+            Some(
+                github_repo
+                    .parent
+                    .map(|parent| parent.full_name)
+                    .unwrap_or_default(),
+            )
+        } else {
+            None
+        },
+    })
+}
+
+/// sends an unauthenticated GET request to a forge API and parses the JSON response
+fn api_get<T: serde::de::DeserializeOwned>(
+    http_client: HttpClient,
+    request_url: &str,
+) -> Option<T> {
+    // send the request
+    let resp = match http_client.send(http_client.get(request_url)) {
+        Err(err) => {
+            eprintln!("{}", err);
+            return None;
+        }
+        Ok(resp) => resp,
+    };
+
+    if !resp.status.is_success() {
+        eprintln!("dephell: request failed");
+        eprintln!("query: {}", request_url);
+        eprintln!("status: {}", resp.status);
+        eprintln!("text: {:?}", resp.body);
+        return None;
+    }
+    let resp: serde_json::Result<T> = resp.json();
+    match resp {
+        Ok(x) => Some(x),
+        Err(err) => {
+            eprintln!("dephell: {}", err);
+            None
+        }
+    }
+}
+
+/// get information about a gitlab repo (stars, last commit, etc.),
+/// works with gitlab.com as well as self-hosted instances
+pub fn get_gitlab_repo(http_client: HttpClient, repo_url: &RepoUrl) -> Option<RepoStats> {
+    #[derive(Deserialize, Debug)]
+    pub struct ForkedFrom {
+        path_with_namespace: String,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct Project {
+        star_count: u64,
+        default_branch: Option<String>,
+        #[serde(default)]
+        archived: bool,
+        forked_from_project: Option<ForkedFrom>,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct Commit {
+        committed_date: String,
+    }
+
+    // repo information
+    let project_id = repo_url.full_name().replace("/", "%2F");
+    let api_url = format!("https://{}/api/v4/projects/{}", repo_url.host, project_id);
+    let project: Project = api_get(http_client.clone(), &api_url)?;
+
+    // last commit on the default branch
+    let last_commit = project.default_branch.as_ref().and_then(|default_branch| {
+        let request_url = format!(
+            "{}/repository/commits?ref_name={}&per_page=1",
+            api_url, default_branch
+        );
+        let commits: Vec<Commit> = api_get(http_client, &request_url)?;
+        commits
+            .first()
+            .and_then(|commit| format_date(&commit.committed_date))
+    });
+
+    Some(RepoStats {
+        stars: Some(project.star_count),
+        last_commit,
+        archived: project.archived,
+        fork_of: project
+            .forked_from_project
+            .map(|forked_from| forked_from.path_with_namespace),
+    })
+}
+
+/// get information about a gitea repo (stars, last commit, etc.),
+/// this is what codeberg.org runs
+pub fn get_gitea_repo(http_client: HttpClient, repo_url: &RepoUrl) -> Option<RepoStats> {
+    #[derive(Deserialize, Debug)]
+    pub struct Parent {
+        full_name: String,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct GiteaRepo {
+        stars_count: u64,
+        default_branch: String,
+        archived: bool,
+        parent: Option<Parent>,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct Committer {
+        date: String,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct Commit {
+        committer: Committer,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct CommitInfo {
+        commit: Commit,
     }
 
-    // create request to github API
+    // repo information
+    let api_url = format!(
+        "https://{}/api/v1/repos/{}",
+        repo_url.host,
+        repo_url.full_name()
+    );
+    let gitea_repo: GiteaRepo = api_get(http_client.clone(), &api_url)?;
+
+    // last commit on the default branch
     let request_url = format!(
-        "https://api.github.com/repos/{}",
-        repo.trim_end_matches(".git")
+        "{}/commits?sha={}&limit=1",
+        api_url, gitea_repo.default_branch
     );
-    let mut request = http_client.get(&request_url);
+    let commit_infos: Option<Vec<CommitInfo>> = api_get(http_client, &request_url);
+    let last_commit = commit_infos.and_then(|commit_infos| {
+        commit_infos
+            .first()
+            .and_then(|info| format_date(&info.commit.committer.date))
+    });
+
+    Some(RepoStats {
+        stars: Some(gitea_repo.stars_count),
+        last_commit,
+        archived: gitea_repo.archived,
+        fork_of: gitea_repo.parent.map(|parent| parent.full_name),
+    })
+}
+
+/// get information about a sourcehut repo,
+/// sourcehut's API requires a token so we use the public RSS feed of the commit log
+/// (there are no stars on sourcehut)
+pub fn get_sourcehut_repo(http_client: HttpClient, repo_url: &RepoUrl) -> Option<RepoStats> {
+    let request_url = format!("{}/log/rss.xml", repo_url.url());
+    let resp = match http_client.send(http_client.get(&request_url)) {
+        Err(err) => {
+            eprintln!("{}", err);
+            return None;
+        }
+        Ok(resp) => resp,
+    };
+    if !resp.status.is_success() {
+        eprintln!("dephell: sourcehut request failed");
+        eprintln!("query: {}", request_url);
+        eprintln!("status: {}", resp.status);
+        return None;
+    }
+    let rss = resp.body;
+
+    // the first item of the feed is the last commit
+    let item = &rss[rss.find("<item>")?..];
+    let pub_date = &item[item.find("<pubDate>")? + "<pubDate>".len()..];
+    let pub_date = &pub_date[..pub_date.find("</pubDate>")?];
+    let last_commit = chrono::DateTime::parse_from_rfc2822(pub_date.trim())
+        .ok()
+        .map(|date| date.format("%Y-%m-%d").to_string());
+
+    Some(RepoStats {
+        last_commit,
+        ..Default::default()
+    })
+}
+
+/// get information about a bitbucket repo (watchers, last commit, etc.)
+pub fn get_bitbucket_repo(http_client: HttpClient, repo_url: &RepoUrl) -> Option<RepoStats> {
+    #[derive(Deserialize, Debug)]
+    pub struct Branch {
+        name: String,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct Parent {
+        full_name: String,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct BitbucketRepo {
+        mainbranch: Option<Branch>,
+        parent: Option<Parent>,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct Page<T> {
+        size: Option<u64>,
+        values: Vec<T>,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct Commit {
+        date: String,
+    }
+
+    // repo information
+    let api_url = format!(
+        "https://api.bitbucket.org/2.0/repositories/{}",
+        repo_url.full_name()
+    );
+    let bitbucket_repo: BitbucketRepo = api_get(http_client.clone(), &api_url)?;
+
+    // number of watchers (bitbucket doesn't have stars)
+    let request_url = format!("{}/watchers?pagelen=1", api_url);
+    let watchers: Option<Page<serde_json::Value>> = api_get(http_client.clone(), &request_url);
+
+    // last commit on the main branch
+    let last_commit = bitbucket_repo.mainbranch.as_ref().and_then(|mainbranch| {
+        let request_url = format!("{}/commits/{}?pagelen=1", api_url, mainbranch.name);
+        let commits: Page<Commit> = api_get(http_client, &request_url)?;
+        commits
+            .values
+            .first()
+            .and_then(|commit| format_date(&commit.date))
+    });
+
+    Some(RepoStats {
+        stars: watchers.and_then(|watchers| watchers.size),
+        last_commit,
+        archived: false,
+        fork_of: bitbucket_repo.parent.map(|parent| parent.full_name),
+    })
+}
+
+/// RepoHost is a forge that we know how to get repository statistics from
+pub trait RepoHost {
+    /// get statistics about a repository hosted on this forge
+    fn get_repo_stats(&self, http_client: HttpClient, repo_url: &RepoUrl) -> Option<RepoStats>;
+}
+
+/// github.com or a GitHub Enterprise instance (requires a token)
+pub struct GithubHost<'a> {
+    pub github: GithubApi<'a>,
+}
+
+impl<'a> RepoHost for GithubHost<'a> {
+    fn get_repo_stats(&self, http_client: HttpClient, repo_url: &RepoUrl) -> Option<RepoStats> {
+        get_github_repo(http_client, &self.github, &repo_url.full_name())
+    }
+}
+
+/// gitlab.com and self-hosted gitlab instances
+pub struct GitlabHost;
+
+impl RepoHost for GitlabHost {
+    fn get_repo_stats(&self, http_client: HttpClient, repo_url: &RepoUrl) -> Option<RepoStats> {
+        get_gitlab_repo(http_client, repo_url)
+    }
+}
+
+/// codeberg.org, gitea.com and self-hosted gitea instances
+pub struct GiteaHost;
+
+impl RepoHost for GiteaHost {
+    fn get_repo_stats(&self, http_client: HttpClient, repo_url: &RepoUrl) -> Option<RepoStats> {
+        get_gitea_repo(http_client, repo_url)
+    }
+}
+
+/// git.sr.ht
+pub struct SourcehutHost;
+
+impl RepoHost for SourcehutHost {
+    fn get_repo_stats(&self, http_client: HttpClient, repo_url: &RepoUrl) -> Option<RepoStats> {
+        get_sourcehut_repo(http_client, repo_url)
+    }
+}
+
+/// bitbucket.org
+pub struct BitbucketHost;
+
+impl RepoHost for BitbucketHost {
+    fn get_repo_stats(&self, http_client: HttpClient, repo_url: &RepoUrl) -> Option<RepoStats> {
+        get_bitbucket_repo(http_client, repo_url)
+    }
+}
+
+/// returns the forge hosting a repository, if we know how to query it
+pub fn get_repo_host<'a>(
+    repo_url: &RepoUrl,
+    github: Option<GithubApi<'a>>,
+    gitlab_host: Option<&str>,
+) -> Option<Box<dyn RepoHost + 'a>> {
+    if let Some(github) = github {
+        if github.hosts(repo_url) {
+            return Some(Box::new(GithubHost { github }));
+        }
+    }
+    let host = repo_url.host.as_str();
+    match host {
+        "github.com" => None, // we need a token
+        "gitlab.com" => Some(Box::new(GitlabHost)),
+        "codeberg.org" | "gitea.com" => Some(Box::new(GiteaHost)),
+        "git.sr.ht" => Some(Box::new(SourcehutHost)),
+        "bitbucket.org" => Some(Box::new(BitbucketHost)),
+        _ if Some(host) == gitlab_host => Some(Box::new(GitlabHost)),
+        _ if host.starts_with("gitea.") => Some(Box::new(GiteaHost)),
+        _ => None,
+    }
+}
+
+/// get the number of contributors of a github repo,
+/// and the share of commits authored by its top contributor (the bus factor)
+pub fn get_github_contributors(
+    http_client: HttpClient,
+    github: &GithubApi,
+    repo: &str,
+) -> Option<(u64, f64)> {
+    #[derive(Deserialize, Debug)]
+    pub struct Contributor {
+        contributions: u64,
+    }
+
+    // github only returns the first 100 contributors per page,
+    // which is plenty to compute the share of the top contributor
+    let request_url = github.rest_url(&format!(
+        "repos/{}/contributors?per_page=100",
+        repo.trim_end_matches(".git")
+    ));
+    let contributors: Vec<Contributor> = github_get(http_client, github, &request_url)?;
+
+    let total_commits: u64 = contributors.iter().map(|c| c.contributions).sum();
+    let top_commits = contributors.iter().map(|c| c.contributions).max()?;
+    if total_commits == 0 {
+        return None;
+    }
+    let bus_factor = top_commits as f64 / total_commits as f64;
+    Some((contributors.len() as u64, bus_factor))
+}
+
+/// GithubActivity contains information about the issues and pull requests of a github repo
+#[derive(Debug)]
+pub struct GithubActivity {
+    /// number of open issues
+    pub open_issues: u64,
+    /// number of open pull requests
+    pub open_pull_requests: u64,
+    /// median time (in hours) it took to get a first response on recent issues
+    pub median_response_time: Option<f64>,
+}
+
+/// get issue and pull request activity of a github repo
+pub fn get_github_activity(
+    http_client: HttpClient,
+    github: &GithubApi,
+    repo: &str,
+) -> Option<GithubActivity> {
+    #[derive(Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    struct Count {
+        total_count: u64,
+    }
+    #[derive(Deserialize, Debug)]
+    struct Author {
+        login: String,
+    }
+    #[derive(Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    struct Comment {
+        created_at: String,
+        author: Option<Author>,
+    }
+    #[derive(Deserialize, Debug)]
+    struct Comments {
+        nodes: Vec<Comment>,
+    }
+    #[derive(Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    struct Issue {
+        created_at: String,
+        author: Option<Author>,
+        comments: Comments,
+    }
+    #[derive(Deserialize, Debug)]
+    struct Issues {
+        nodes: Vec<Issue>,
+    }
+    #[derive(Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    struct Repository {
+        open_issues: Count,
+        open_pull_requests: Count,
+        recent_issues: Issues,
+    }
+    #[derive(Deserialize, Debug)]
+    struct Data {
+        repository: Repository,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct Response {
+        data: Data,
+    }
+    let mut owner_and_name = repo.trim_end_matches(".git").splitn(2, '/');
+    let owner = owner_and_name.next()?;
+    let name = owner_and_name.next()?;
+
+    // create request to github GraphQL API
+    let query = r#"
+        query($owner: String!, $name: String!) {
+            repository(owner: $owner, name: $name) {
+                openIssues: issues(states: OPEN) { totalCount }
+                openPullRequests: pullRequests(states: OPEN) { totalCount }
+                recentIssues: issues(first: 20, orderBy: { field: CREATED_AT, direction: DESC }) {
+                    nodes {
+                        createdAt
+                        author { login }
+                        comments(first: 10) { nodes { createdAt author { login } } }
+                    }
+                }
+            }
+        }
+    "#;
+    let mut request = http_client
+        .post(&github.graphql_url())
+        .json(&serde_json::json!({
+            "query": query,
+            "variables": { "owner": owner, "name": name },
+        }));
 
     // use the github token
-    let (username, token) = github_token;
-    request = request.basic_auth(username, Some(token));
+    request = request.bearer_auth(github.token);
+
+    // send the request
+    let resp = match http_client.send(request) {
+        Err(err) => {
+            eprintln!("{}", err);
+            return None;
+        }
+        Ok(resp) => resp,
+    };
+    // parse response
+    if !resp.status.is_success() {
+        eprintln!("dephell: github request failed");
+        eprintln!("status: {}", resp.status);
+        eprintln!("text: {:?}", resp.body);
+        return None;
+    }
+    let resp: serde_json::Result<Response> = resp.json();
+    let repository = match resp {
+        Err(err) => {
+            eprintln!("dephell: {}", err);
+            return None;
+        }
+        Ok(resp) => resp.data.repository,
+    };
+
+    // time to first response (a comment from someone else than the author),
+    // issues that never got a response are counted as waiting until now
+    let now = chrono::Utc::now();
+    let login = |author: &Option<Author>| author.as_ref().map(|author| author.login.clone());
+    let mut response_times: Vec<f64> = repository
+        .recent_issues
+        .nodes
+        .iter()
+        .filter_map(|issue| {
+            let created_at = chrono::DateTime::parse_from_rfc3339(&issue.created_at).ok()?;
+            let first_response = issue
+                .comments
+                .nodes
+                .iter()
+                .find(|comment| login(&comment.author) != login(&issue.author))
+                .and_then(|comment| chrono::DateTime::parse_from_rfc3339(&comment.created_at).ok())
+                .map(|date| date.with_timezone(&chrono::Utc))
+                .unwrap_or(now);
+            let response_time = first_response.signed_duration_since(created_at);
+            Some(response_time.num_minutes() as f64 / 60.0)
+        })
+        .collect();
+    response_times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let median_response_time = if response_times.is_empty() {
+        None
+    } else if response_times.len() % 2 == 0 {
+        let middle = response_times.len() / 2;
+        Some((response_times[middle - 1] + response_times[middle]) / 2.0)
+    } else {
+        Some(response_times[response_times.len() / 2])
+    };
 
+    Some(GithubActivity {
+        open_issues: repository.open_issues.total_count,
+        open_pull_requests: repository.open_pull_requests.total_count,
+        median_response_time,
+    })
+}
+
+/// checks which of the given paths exist on the default branch of a github repo
+/// (all paths are checked with a single GraphQL query)
+pub fn get_github_paths(
+    http_client: HttpClient,
+    github: &GithubApi,
+    repo: &str,
+    paths: &[String],
+) -> Option<Vec<bool>> {
+    #[derive(Deserialize, Debug)]
+    struct Data {
+        // each path is aliased `path0`, `path1`, etc. and is null if it doesn't exist
+        repository: HashMap<String, Option<serde_json::Value>>,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct Response {
+        data: Data,
+    }
+    let mut owner_and_name = repo.trim_end_matches(".git").splitn(2, '/');
+    let owner = owner_and_name.next()?;
+    let name = owner_and_name.next()?;
+
+    // create request to github GraphQL API
+    let mut objects = String::new();
+    for (idx, path) in paths.iter().enumerate() {
+        let expression = serde_json::to_string(&format!("HEAD:{}", path)).ok()?;
+        objects.push_str(&format!(
+            "path{}: object(expression: {}) {{ id }}\n",
+            idx, expression
+        ));
+    }
+    let query = format!(
+        "query($owner: String!, $name: String!) {{ repository(owner: $owner, name: $name) {{ {} }} }}",
+        objects
+    );
+    let mut request = http_client
+        .post(&github.graphql_url())
+        .json(&serde_json::json!({
+            "query": query,
+            "variables": { "owner": owner, "name": name },
+        }));
+
+    // use the github token
+    request = request.bearer_auth(github.token);
+
+    // send the request
+    let resp = match http_client.send(request) {
+        Err(err) => {
+            eprintln!("{}", err);
+            return None;
+        }
+        Ok(resp) => resp,
+    };
+    // parse response
+    if !resp.status.is_success() {
+        eprintln!("dephell: github request failed");
+        eprintln!("status: {}", resp.status);
+        eprintln!("text: {:?}", resp.body);
+        return None;
+    }
+    let resp: serde_json::Result<Response> = resp.json();
+    let repository = match resp {
+        Err(err) => {
+            eprintln!("dephell: {}", err);
+            return None;
+        }
+        Ok(resp) => resp.data.repository,
+    };
+
+    let exists = (0..paths.len())
+        .map(|idx| {
+            repository
+                .get(&format!("path{}", idx))
+                .map(|object| object.is_some())
+                .unwrap_or(false)
+        })
+        .collect();
+    Some(exists)
+}
+
+/// files and directories that indicate continuous integration
+const CI_PATHS: &[&str] = &[
+    ".github/workflows",
+    ".travis.yml",
+    ".gitlab-ci.yml",
+    ".circleci",
+    ".cirrus.yml",
+    ".buildkite",
+    "azure-pipelines.yml",
+    "appveyor.yml",
+];
+
+/// directories that indicate fuzzing (cargo-fuzz, ClusterFuzzLite)
+const FUZZING_PATHS: &[&str] = &["fuzz", ".clusterfuzzlite"];
+
+/// checks if a github repo has continuous integration and fuzzing set up,
+/// fuzzing is also detected if the crate (or the repo) is a project of OSS-Fuzz
+pub fn get_ci_and_fuzzing(
+    http_client: HttpClient,
+    github: &GithubApi,
+    repo_url: &RepoUrl,
+    crate_name: &str,
+) -> Option<(bool, bool)> {
+    let mut paths: Vec<String> = CI_PATHS.iter().map(|path| path.to_string()).collect();
+    paths.extend(FUZZING_PATHS.iter().map(|path| path.to_string()));
+    // in a monorepo, the fuzz directory might live next to the crate
+    if let Some(sub_path) = &repo_url.sub_path {
+        paths.push(format!("{}/fuzz", sub_path.trim_end_matches('/')));
+    }
+    let exists = get_github_paths(http_client.clone(), github, &repo_url.full_name(), &paths)?;
+    let has_ci = exists[..CI_PATHS.len()].iter().any(|exists| *exists);
+    let mut has_fuzzing = exists[CI_PATHS.len()..].iter().any(|exists| *exists);
+
+    // OSS-Fuzz projects are on github.com (not on GitHub Enterprise instances)
+    if !has_fuzzing && repo_url.host == "github.com" {
+        let projects = vec![
+            format!("projects/{}", crate_name.to_lowercase()),
+            format!("projects/{}", repo_url.name.to_lowercase()),
+        ];
+        let oss_fuzz = get_github_paths(http_client, github, "google/oss-fuzz", &projects);
+        has_fuzzing = oss_fuzz.unwrap_or_default().iter().any(|exists| *exists);
+    }
+
+    Some((has_ci, has_fuzzing))
+}
+
+/// where github looks for community health files
+const COMMUNITY_DIRS: &[&str] = &["", ".github/", "docs/"];
+
+/// RepoHygiene contains the community health files found in a github repo
+#[derive(Debug)]
+pub struct RepoHygiene {
+    /// is there a SECURITY.md (vulnerability disclosure policy)?
+    pub security_policy: bool,
+    /// is there a CODE_OF_CONDUCT.md?
+    pub code_of_conduct: bool,
+    /// is there a .github/FUNDING.yml?
+    pub funding: bool,
+}
+
+/// checks if a github repo has a security policy, a code of conduct and funding metadata
+pub fn get_repo_hygiene(
+    http_client: HttpClient,
+    github: &GithubApi,
+    repo: &str,
+) -> Option<RepoHygiene> {
+    let files = ["SECURITY.md", "CODE_OF_CONDUCT.md"];
+    let mut paths: Vec<String> = files
+        .iter()
+        .flat_map(|file| {
+            COMMUNITY_DIRS
+                .iter()
+                .map(move |dir| format!("{}{}", dir, file))
+        })
+        .collect();
+    paths.push(".github/FUNDING.yml".to_string());
+    let exists = get_github_paths(http_client, github, repo, &paths)?;
+
+    let found = |file_idx: usize| {
+        let start = file_idx * COMMUNITY_DIRS.len();
+        exists[start..start + COMMUNITY_DIRS.len()]
+            .iter()
+            .any(|exists| *exists)
+    };
+    Some(RepoHygiene {
+        security_policy: found(0),
+        code_of_conduct: found(1),
+        funding: exists[exists.len() - 1],
+    })
+}
+
+/// get number of maintainers in the last 6 months
+pub fn get_active_maintainers(
+    http_client: HttpClient,
+    github: &GithubApi,
+    repo: &str,
+) -> Option<u64> {
+    #[derive(Deserialize, Debug)]
+    pub struct Author {
+        email: String,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct Commit {
+        author: Author,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct CommitInfo {
+        commit: Commit,
+    }
+    // create request to crates.io API
+    let six_months_ago = chrono::Utc::now()
+        .checked_sub_signed(chrono::Duration::weeks(4 * 6)) // 6 months
+        .unwrap()
+        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    let request_url = github.rest_url(&format!(
+        "repos/{}/commits?since={}",
+        repo.trim_end_matches(".git"),
+        six_months_ago,
+    ));
+    let mut request = http_client.get(&request_url);
+
+    // use the github token
+    request = request.bearer_auth(github.token);
+
+    // send the request
+    let resp = match http_client.send(request) {
+        Err(err) => {
+            eprintln!("{}", err);
+            return None;
+        }
+        Ok(resp) => resp,
+    };
+    // parse response
+    if !resp.status.is_success() {
+        eprintln!("dephell: crates.io request failed");
+        eprintln!("status: {}", resp.status);
+        eprintln!("text: {:?}", resp.body);
+        return None;
+    }
+    let resp: serde_json::Result<Vec<CommitInfo>> = resp.json();
+    match resp {
+        Err(err) => {
+            eprintln!("dephell: {}", err);
+            None
+        }
+        Ok(commit_infos) => {
+            let mut commiters = HashSet::new();
+            for commit_info in commit_infos {
+                commiters.insert(commit_info.commit.author.email);
+            }
+            Some(commiters.len() as u64)
+        }
+    }
+}
+
+/// CratesIoResponse is used to parse the response from crates.io
+pub fn get_crates_io_dependent(http_client: HttpClient, crate_name: &str) -> Option<u64> {
+    #[derive(Deserialize, Debug)]
+    struct Meta {
+        total: u64,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct Response {
+        meta: Meta,
+    }
+    // create request to crates.io API
+    let request_url = format!(
+        "https://crates.io/api/v1/crates/{}/reverse_dependencies",
+        crate_name,
+    );
+    let request = http_client.get(&request_url);
+    // send the request
+    let resp = match http_client.send(request) {
+        Err(err) => {
+            eprintln!("{}", err);
+            return None;
+        }
+        Ok(resp) => resp,
+    };
+    // parse response
+    if !resp.status.is_success() {
+        eprintln!("dephell: crates.io request failed");
+        eprintln!("query: {}", request_url);
+        eprintln!("status: {}", resp.status);
+        eprintln!("text: {:?}", resp.body);
+        return None;
+    }
+    let resp: serde_json::Result<Response> = resp.json();
+    match resp {
+        Err(err) => {
+            eprintln!("dephell: {}", err);
+            None
+        }
+        Ok(x) => Some(x.meta.total),
+    }
+}
+
+/// CratesIoCrate contains information about a crate on crates.io
+#[derive(Deserialize, Debug)]
+pub struct CratesIoCrate {
+    /// last time a version was published (YYYY-MM-DD)
+    pub updated_at: String,
+    /// number of downloads, all time
+    pub downloads: u64,
+    /// number of downloads in the last 90 days
+    pub recent_downloads: Option<u64>,
+    /// every version published (most recent first)
+    #[serde(skip)]
+    pub versions: Vec<PublishedVersion>,
+}
+
+/// PublishedVersion is a version of a crate published on crates.io
+#[derive(Deserialize, Debug)]
+pub struct PublishedVersion {
+    /// the version
+    pub num: String,
+    /// when it was published
+    pub created_at: String,
+    /// has it been yanked?
+    pub yanked: bool,
+    /// who published it (not available for old versions)
+    pub published_by: Option<CratesIoUser>,
+    /// size of the .crate archive, in bytes
+    pub crate_size: Option<u64>,
+}
+
+/// obtains information about a crate on crates.io (last update, downloads, etc.)
+pub fn get_crates_io_crate(http_client: HttpClient, crate_name: &str) -> Option<CratesIoCrate> {
+    #[derive(Deserialize, Debug)]
+    pub struct Response {
+        #[serde(rename = "crate")]
+        crate_: CratesIoCrate,
+        versions: Vec<PublishedVersion>,
+    }
+    // create request to crates.io API
+    let request_url = format!("https://crates.io/api/v1/crates/{}", crate_name,);
+    let request = http_client.get(&request_url);
+    // send the request
+    let resp = match http_client.send(request) {
+        Err(err) => {
+            eprintln!("{}", err);
+            return None;
+        }
+        Ok(resp) => resp,
+    };
+    // parse response
+    if !resp.status.is_success() {
+        eprintln!("dephell: crates.io request failed");
+        eprintln!("status: {}", resp.status);
+        eprintln!("text: {:?}", resp.body);
+        return None;
+    }
+    let resp: serde_json::Result<Response> = resp.json();
+    match resp {
+        Err(err) => {
+            eprintln!("dephell: {}", err);
+            None
+        }
+        Ok(resp) => {
+            let mut crates_io_crate = resp.crate_;
+            crates_io_crate.versions = resp.versions;
+            crates_io_crate
+                .versions
+                .sort_by(|a, b| b.created_at.cmp(&a.created_at));
+            if let Some(formatted_date) = format_date(&crates_io_crate.updated_at) {
+                crates_io_crate.updated_at = formatted_date;
+            }
+            Some(crates_io_crate)
+        }
+    }
+}
+
+/// obtains the SourceRank of a crate from libraries.io (requires an API key)
+pub fn get_librariesio_sourcerank(
+    http_client: HttpClient,
+    api_key: &str,
+    crate_name: &str,
+) -> Option<u64> {
+    #[derive(Deserialize, Debug)]
+    pub struct Response {
+        rank: u64,
+    }
+    // create request to libraries.io API
+    let request_url = format!("https://libraries.io/api/cargo/{}", crate_name);
+    let request = http_client.get(&request_url).query(&[("api_key", api_key)]);
+    // send the request
+    let resp = match http_client.send(request) {
+        Err(err) => {
+            eprintln!("{}", err);
+            return None;
+        }
+        Ok(resp) => resp,
+    };
+    // parse response
+    if !resp.status.is_success() {
+        eprintln!("dephell: libraries.io request failed");
+        eprintln!("query: {}", request_url);
+        eprintln!("status: {}", resp.status);
+        eprintln!("text: {:?}", resp.body);
+        return None;
+    }
+    let resp: serde_json::Result<Response> = resp.json();
+    match resp {
+        Err(err) => {
+            eprintln!("dephell: {}", err);
+            None
+        }
+        Ok(resp) => Some(resp.rank),
+    }
+}
+
+/// checks if docs.rs successfully built the documentation of a specific version of a crate
+pub fn get_docs_rs_status(
+    http_client: HttpClient,
+    crate_name: &str,
+    version: &str,
+) -> Option<bool> {
+    #[derive(Deserialize, Debug)]
+    pub struct Response {
+        doc_status: bool,
+    }
+    // create request to docs.rs API
+    let request_url = format!(
+        "https://docs.rs/crate/{}/{}/status.json",
+        crate_name, version
+    );
+    let request = http_client.get(&request_url);
+    // send the request
+    let resp = match http_client.send(request) {
+        Err(err) => {
+            eprintln!("{}", err);
+            return None;
+        }
+        Ok(resp) => resp,
+    };
+    // parse response
+    if !resp.status.is_success() {
+        eprintln!("dephell: docs.rs request failed");
+        eprintln!("query: {}", request_url);
+        eprintln!("status: {}", resp.status);
+        eprintln!("text: {:?}", resp.body);
+        return None;
+    }
+    let resp: serde_json::Result<Response> = resp.json();
+    match resp {
+        Err(err) => {
+            eprintln!("dephell: {}", err);
+            None
+        }
+        Ok(resp) => Some(resp.doc_status),
+    }
+}
+
+/// obtains the owners (users and teams) of a crate on crates.io
+pub fn get_crates_io_owners(http_client: HttpClient, crate_name: &str) -> Option<Vec<String>> {
+    #[derive(Deserialize, Debug)]
+    pub struct Response {
+        users: Vec<CratesIoUser>,
+    }
+    // create request to crates.io API
+    let request_url = format!("https://crates.io/api/v1/crates/{}/owners", crate_name);
+    let request = http_client.get(&request_url);
+    // send the request
+    let resp = match http_client.send(request) {
+        Err(err) => {
+            eprintln!("{}", err);
+            return None;
+        }
+        Ok(resp) => resp,
+    };
+    // parse response
+    if !resp.status.is_success() {
+        eprintln!("dephell: crates.io request failed");
+        eprintln!("query: {}", request_url);
+        eprintln!("status: {}", resp.status);
+        eprintln!("text: {:?}", resp.body);
+        return None;
+    }
+    let resp: serde_json::Result<Response> = resp.json();
+    match resp {
+        Err(err) => {
+            eprintln!("dephell: {}", err);
+            None
+        }
+        Ok(resp) => Some(resp.users.into_iter().map(|user| user.login).collect()),
+    }
+}
+
+/// returns the publisher of the most recent version if they never published a previous version,
+/// a new publisher can be the sign of an ownership handoff (a classic precursor to compromise)
+pub fn get_new_publisher(versions: &[PublishedVersion]) -> Option<String> {
+    // versions are sorted from the most recent
+    let (latest, previous) = versions.split_first()?;
+    let publisher = &latest.published_by.as_ref()?.login;
+    let previous_publishers: HashSet<&str> = previous
+        .iter()
+        .filter_map(|version| version.published_by.as_ref())
+        .map(|user| user.login.as_str())
+        .collect();
+    if previous_publishers.is_empty() || previous_publishers.contains(publisher.as_str()) {
+        None
+    } else {
+        Some(publisher.to_owned())
+    }
+}
+
+/// returns the date of the latest release (YYYY-MM-DD),
+/// and the average number of days between releases over the last year
+pub fn get_release_cadence(versions: &[PublishedVersion]) -> (Option<String>, Option<f64>) {
+    // versions are sorted from the most recent
+    let release_dates: Vec<_> = versions
+        .iter()
+        .filter_map(|version| chrono::DateTime::parse_from_rfc3339(&version.created_at).ok())
+        .collect();
+    let latest_release = release_dates
+        .first()
+        .map(|date| date.format("%Y-%m-%d").to_string());
+
+    // intervals between releases that happened in the last year
+    let one_year_ago = chrono::Utc::now() - chrono::Duration::days(365);
+    let intervals: Vec<i64> = release_dates
+        .windows(2)
+        .filter(|releases| releases[0] > one_year_ago)
+        .map(|releases| releases[0].signed_duration_since(releases[1]).num_days())
+        .collect();
+    let release_interval = if intervals.is_empty() {
+        None
+    } else {
+        Some(intervals.iter().sum::<i64>() as f64 / intervals.len() as f64)
+    };
+
+    (latest_release, release_interval)
+}
+
+/// VersionsBehind counts the releases published after a version
+#[derive(Debug, Default)]
+pub struct VersionsBehind {
+    /// the newest (non-yanked, non-prerelease) version
+    pub latest: String,
+    /// number of newer semver-incompatible series (e.g. 2.x and 3.x for 1.2.3, 0.4.x for 0.3.1)
+    pub major: u64,
+    /// number of newer minor versions in the same series
+    pub minor: u64,
+    /// number of newer patch versions of the same minor version
+    pub patch: u64,
+}
+
+/// compares a version to the releases published on crates.io (yanked and pre-releases excluded)
+pub fn get_versions_behind(versions: &[PublishedVersion], version: &str) -> Option<VersionsBehind> {
+    // the "series" of semver-compatible versions a version belongs to
+    let series = |version: &semver::Version| {
+        if version.major == 0 {
+            (0, version.minor)
+        } else {
+            (version.major, 0)
+        }
+    };
+
+    let version = semver::Version::parse(version).ok()?;
+    let releases: Vec<semver::Version> = versions
+        .iter()
+        .filter(|release| !release.yanked)
+        .filter_map(|release| semver::Version::parse(&release.num).ok())
+        .filter(|release| !release.is_prerelease())
+        .collect();
+    let latest = releases.iter().max()?;
+
+    let newer = releases.iter().filter(|release| *release > &version);
+    let mut major = HashSet::new();
+    let mut minor = HashSet::new();
+    let mut patch = HashSet::new();
+    for release in newer {
+        if series(release) != series(&version) {
+            major.insert(series(release));
+        } else if release.minor != version.minor {
+            minor.insert(release.minor);
+        } else {
+            patch.insert(release.patch);
+        }
+    }
+
+    Some(VersionsBehind {
+        latest: latest.to_string(),
+        major: major.len() as u64,
+        minor: minor.len() as u64,
+        patch: patch.len() as u64,
+    })
+}
+
+/// CratesIoVersion contains information about a specific version of a crate on crates.io
+#[derive(Deserialize, Debug)]
+pub struct CratesIoVersion {
+    /// has this version been yanked?
+    pub yanked: bool,
+    /// who published this version (not set for trusted publishing)
+    pub published_by: Option<CratesIoUser>,
+    /// set if this version was published via trusted publishing (from CI)
+    pub trustpub_data: Option<TrustedPublishing>,
+}
+
+/// a crates.io user
+#[derive(Deserialize, Debug)]
+pub struct CratesIoUser {
+    pub login: String,
+}
+
+/// metadata of a version published via crates.io trusted publishing
+#[derive(Deserialize, Debug)]
+pub struct TrustedPublishing {
+    /// CI provider (e.g. "github")
+    pub provider: String,
+    /// repository the CI workflow ran in
+    pub repository: Option<String>,
+    /// commit the CI workflow ran on
+    pub sha: Option<String>,
+}
+
+/// obtains information about a specific version of a crate on crates.io
+pub fn get_crates_io_version(
+    http_client: HttpClient,
+    crate_name: &str,
+    version: &str,
+) -> Option<CratesIoVersion> {
+    #[derive(Deserialize, Debug)]
+    pub struct Response {
+        version: CratesIoVersion,
+    }
+    // create request to crates.io API
+    let request_url = format!("https://crates.io/api/v1/crates/{}/{}", crate_name, version);
+    let request = http_client.get(&request_url);
+    // send the request
+    let resp = match http_client.send(request) {
+        Err(err) => {
+            eprintln!("{}", err);
+            return None;
+        }
+        Ok(resp) => resp,
+    };
+    // parse response
+    if !resp.status.is_success() {
+        eprintln!("dephell: crates.io request failed");
+        eprintln!("query: {}", request_url);
+        eprintln!("status: {}", resp.status);
+        eprintln!("text: {:?}", resp.body);
+        return None;
+    }
+    let resp: serde_json::Result<Response> = resp.json();
+    match resp {
+        Err(err) => {
+            eprintln!("dephell: {}", err);
+            None
+        }
+        Ok(resp) => Some(resp.version),
+    }
+}
+
+/// normalizes the severity of an advisory to `low`, `medium`, `high` or `critical`
+/// (github and OSV say `moderate` for `medium`)
+fn normalize_severity(severity: &str) -> String {
+    match severity.to_lowercase().as_str() {
+        "moderate" => "medium".to_owned(),
+        severity => severity.to_owned(),
+    }
+}
+
+/// obtains the RustSec advisories affecting a specific version of a crate
+pub fn get_rustsec_advisories(
+    advisory_db: &rustsec::Database,
+    crate_name: &str,
+    version: &str,
+) -> Vec<Advisory> {
+    let version = match rustsec::Version::parse(version) {
+        Ok(version) => version,
+        Err(err) => {
+            eprintln!(
+                "dephell: couldn't parse version {} of {}: {}",
+                version, crate_name, err
+            );
+            return Vec::new();
+        }
+    };
+    advisory_db
+        .iter()
+        .filter(|advisory| advisory.metadata.package.as_str() == crate_name)
+        .filter(|advisory| advisory.metadata.withdrawn.is_none())
+        .filter(|advisory| advisory.versions.is_vulnerable(&version))
+        .map(|advisory| Advisory {
+            id: advisory.metadata.id.to_string(),
+            severity: advisory
+                .metadata
+                .cvss
+                .as_ref()
+                .map(|cvss| cvss.severity().as_str().to_owned()),
+            patched_versions: advisory
+                .versions
+                .patched
+                .iter()
+                .map(|req| req.to_string())
+                .collect(),
+            aliases: advisory
+                .metadata
+                .aliases
+                .iter()
+                .map(|alias| alias.to_string())
+                .collect(),
+        })
+        .collect()
+}
+
+/// queries the OSV.dev batch API for a list of (crate, version),
+/// returns the IDs of the vulnerabilities affecting each of them (in the same order)
+pub fn get_osv_vulnerability_ids(
+    http_client: HttpClient,
+    packages: &[(String, String)],
+) -> Option<Vec<Vec<String>>> {
+    #[derive(Deserialize, Debug)]
+    struct Vuln {
+        id: String,
+    }
+    #[derive(Deserialize, Debug)]
+    struct QueryResult {
+        #[serde(default)]
+        vulns: Vec<Vuln>,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct Response {
+        results: Vec<QueryResult>,
+    }
+    // create request to OSV API
+    let queries: Vec<_> = packages
+        .iter()
+        .map(|(name, version)| {
+            serde_json::json!({
+                "package": { "name": name, "ecosystem": "crates.io" },
+                "version": version,
+            })
+        })
+        .collect();
+    let request_url = "https://api.osv.dev/v1/querybatch";
+    let request = http_client
+        .post(request_url)
+        .json(&serde_json::json!({ "queries": queries }));
     // send the request
-    let resp = match request.send() {
+    let resp = match http_client.send(request) {
         Err(err) => {
             eprintln!("{}", err);
             return None;
         }
         Ok(resp) => resp,
     };
-
-    if !resp.status().is_success() {
-        eprintln!("dephell: github request failed");
-        eprintln!("status: {}", resp.status());
-        eprintln!("text: {:?}", resp.text());
+    // parse response
+    if !resp.status.is_success() {
+        eprintln!("dephell: osv.dev request failed");
+        eprintln!("status: {}", resp.status);
+        eprintln!("text: {:?}", resp.body);
         return None;
     }
-    let resp: reqwest::Result<GithubResponse> = resp.json();
+    let resp: serde_json::Result<Response> = resp.json();
     match resp {
-        Ok(x) => Some(x.stargazers_count),
         Err(err) => {
             eprintln!("dephell: {}", err);
             None
         }
+        Ok(resp) => Some(
+            resp.results
+                .into_iter()
+                .map(|result| result.vulns.into_iter().map(|vuln| vuln.id).collect())
+                .collect(),
+        ),
     }
 }
 
-/// get number of maintainers in the last 6 months
-pub fn get_active_maintainers(
-    http_client: reqwest::blocking::Client,
-    github_token: (&str, &str),
-    repo: &str,
-) -> Option<u64> {
+/// fetches a vulnerability from OSV.dev
+pub fn get_osv_vulnerability(http_client: HttpClient, id: &str) -> Option<Advisory> {
     #[derive(Deserialize, Debug)]
-    pub struct Author {
-        email: String,
+    struct Event {
+        fixed: Option<String>,
     }
     #[derive(Deserialize, Debug)]
-    pub struct Commit {
-        author: Author,
+    struct Range {
+        #[serde(default)]
+        events: Vec<Event>,
     }
     #[derive(Deserialize, Debug)]
-    pub struct CommitInfo {
-        commit: Commit,
+    struct Affected {
+        #[serde(default)]
+        ranges: Vec<Range>,
     }
-    // create request to crates.io API
-    let six_months_ago = chrono::Utc::now()
-        .checked_sub_signed(chrono::Duration::weeks(4 * 6)) // 6 months
-        .unwrap()
-        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
-    let request_url = format!(
-        "https://api.github.com/repos/{}/commits?since={}",
-        repo.trim_end_matches(".git"),
-        six_months_ago,
-    );
-    let mut request = http_client.get(&request_url);
-
-    // use the github token
-    let (username, token) = github_token;
-    request = request.basic_auth(username, Some(token));
-
+    #[derive(Deserialize, Debug, Default)]
+    struct DatabaseSpecific {
+        severity: Option<String>,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct Response {
+        id: String,
+        #[serde(default)]
+        aliases: Vec<String>,
+        #[serde(default)]
+        affected: Vec<Affected>,
+        #[serde(default)]
+        database_specific: DatabaseSpecific,
+    }
+    // create request to OSV API
+    let request_url = format!("https://api.osv.dev/v1/vulns/{}", id);
+    let request = http_client.get(&request_url);
     // send the request
-    let resp = match request.send() {
+    let resp = match http_client.send(request) {
         Err(err) => {
             eprintln!("{}", err);
             return None;
@@ -103,49 +1557,125 @@ pub fn get_active_maintainers(
         Ok(resp) => resp,
     };
     // parse response
-    if !resp.status().is_success() {
-        eprintln!("dephell: crates.io request failed");
-        eprintln!("status: {}", resp.status());
-        eprintln!("text: {:?}", resp.text());
+    if !resp.status.is_success() {
+        eprintln!("dephell: osv.dev request failed");
+        eprintln!("query: {}", request_url);
+        eprintln!("status: {}", resp.status);
+        eprintln!("text: {:?}", resp.body);
         return None;
     }
-    let resp: reqwest::Result<Vec<CommitInfo>> = resp.json();
+    let resp: serde_json::Result<Response> = resp.json();
     match resp {
         Err(err) => {
             eprintln!("dephell: {}", err);
             None
         }
-        Ok(commit_infos) => {
-            let mut commiters = HashSet::new();
-            for commit_info in commit_infos {
-                commiters.insert(commit_info.commit.author.email);
-            }
-            Some(commiters.len() as u64)
+        Ok(resp) => {
+            let patched_versions = resp
+                .affected
+                .iter()
+                .flat_map(|affected| affected.ranges.iter())
+                .flat_map(|range| range.events.iter())
+                .filter_map(|event| event.fixed.as_ref())
+                .map(|fixed| format!(">= {}", fixed))
+                .collect();
+            let advisory = Advisory {
+                id: resp.id,
+                severity: resp
+                    .database_specific
+                    .severity
+                    .map(|severity| normalize_severity(&severity)),
+                patched_versions,
+                aliases: resp.aliases,
+            };
+            Some(advisory)
         }
     }
 }
 
-/// CratesIoResponse is used to parse the response from crates.io
-pub fn get_crates_io_dependent(
-    http_client: reqwest::blocking::Client,
+/// obtains the GitHub security advisories (GHSA) affecting some versions of a crate
+/// (they are queried once per crate)
+pub fn get_github_advisories(
+    http_client: HttpClient,
+    github: &GithubApi,
     crate_name: &str,
-) -> Option<u64> {
+    versions: &[String],
+) -> Option<Vec<Advisory>> {
     #[derive(Deserialize, Debug)]
-    struct Meta {
-        total: u64,
+    struct Identifier {
+        value: String,
+    }
+    #[derive(Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    struct GithubAdvisory {
+        ghsa_id: String,
+        identifiers: Vec<Identifier>,
+        withdrawn_at: Option<String>,
+    }
+    #[derive(Deserialize, Debug)]
+    struct PatchedVersion {
+        identifier: String,
+    }
+    #[derive(Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    struct Vulnerability {
+        advisory: GithubAdvisory,
+        severity: String,
+        vulnerable_version_range: String,
+        first_patched_version: Option<PatchedVersion>,
+    }
+    #[derive(Deserialize, Debug)]
+    struct Vulnerabilities {
+        nodes: Vec<Vulnerability>,
+    }
+    #[derive(Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    struct Data {
+        security_vulnerabilities: Vulnerabilities,
     }
     #[derive(Deserialize, Debug)]
     pub struct Response {
-        meta: Meta,
+        data: Data,
     }
-    // create request to crates.io API
-    let request_url = format!(
-        "https://crates.io/api/v1/crates/{}/reverse_dependencies",
-        crate_name,
-    );
-    let request = http_client.get(&request_url);
+    let mut parsed_versions = Vec::new();
+    for version in versions {
+        match semver::Version::parse(version) {
+            Ok(version) => parsed_versions.push(version),
+            Err(err) => eprintln!(
+                "dephell: couldn't parse version {} of {}: {}",
+                version, crate_name, err
+            ),
+        }
+    }
+    if parsed_versions.is_empty() {
+        return None;
+    }
+
+    // create request to github GraphQL API
+    let query = r#"
+        query($package: String!) {
+            securityVulnerabilities(first: 100, ecosystem: RUST, package: $package) {
+                nodes {
+                    advisory { ghsaId identifiers { value } withdrawnAt }
+                    severity
+                    vulnerableVersionRange
+                    firstPatchedVersion { identifier }
+                }
+            }
+        }
+    "#;
+    let mut request = http_client
+        .post(&github.graphql_url())
+        .json(&serde_json::json!({
+            "query": query,
+            "variables": { "package": crate_name },
+        }));
+
+    // use the github token
+    request = request.bearer_auth(github.token);
+
     // send the request
-    let resp = match request.send() {
+    let resp = match http_client.send(request) {
         Err(err) => {
             eprintln!("{}", err);
             return None;
@@ -153,70 +1683,267 @@ pub fn get_crates_io_dependent(
         Ok(resp) => resp,
     };
     // parse response
-    if !resp.status().is_success() {
-        eprintln!("dephell: crates.io request failed");
-        eprintln!("query: {}", request_url);
-        eprintln!("status: {}", resp.status());
-        eprintln!("text: {:?}", resp.text());
+    if !resp.status.is_success() {
+        eprintln!("dephell: github request failed");
+        eprintln!("status: {}", resp.status);
+        eprintln!("text: {:?}", resp.body);
         return None;
     }
-    let resp: reqwest::Result<Response> = resp.json();
-    match resp {
+    let resp: serde_json::Result<Response> = resp.json();
+    let vulnerabilities = match resp {
         Err(err) => {
             eprintln!("dephell: {}", err);
-            None
+            return None;
         }
-        Ok(x) => Some(x.meta.total),
+        Ok(resp) => resp.data.security_vulnerabilities.nodes,
+    };
+
+    // only keep the open advisories affecting our versions
+    // (an advisory has a node per vulnerable range)
+    let mut advisories: Vec<Advisory> = Vec::new();
+    for vuln in vulnerabilities {
+        if vuln.advisory.withdrawn_at.is_some() {
+            continue;
+        }
+        // github uses ranges of the form ">= 0.1.0, < 0.2.0"
+        let affected = semver::VersionReq::parse(&vuln.vulnerable_version_range)
+            .map(|range| parsed_versions.iter().any(|version| range.matches(version)))
+            .unwrap_or(false);
+        if !affected || advisories.iter().any(|a| a.id == vuln.advisory.ghsa_id) {
+            continue;
+        }
+        let ghsa_id = vuln.advisory.ghsa_id;
+        let aliases = vuln
+            .advisory
+            .identifiers
+            .into_iter()
+            .map(|identifier| identifier.value)
+            .filter(|id| id != &ghsa_id)
+            .collect();
+        advisories.push(Advisory {
+            id: ghsa_id,
+            severity: Some(normalize_severity(&vuln.severity)),
+            patched_versions: vuln
+                .first_patched_version
+                .map(|patched| vec![format!(">= {}", patched.identifier)])
+                .unwrap_or_default(),
+            aliases,
+        });
     }
+    Some(advisories)
 }
 
-/// CratesIoResponse is used to parse the response from crates.io
-pub fn get_crates_io_last_updated(
-    http_client: reqwest::blocking::Client,
-    crate_name: &str,
-) -> Option<String> {
+/// a list of popular crates that are likely to be typosquatted
+const POPULAR_CRATES: &str = include_str!("../data/popular_crates.txt");
+
+/// normalizes a crate name by undoing common substitutions used by typosquatters
+fn normalize_crate_name(crate_name: &str) -> String {
+    let mut normalized = String::with_capacity(crate_name.len());
+    for c in crate_name.to_lowercase().chars() {
+        let c = match c {
+            '_' => '-',
+            '0' => 'o',
+            '1' => 'l',
+            '3' => 'e',
+            '5' => 's',
+            c => c,
+        };
+        // collapse doubled letters (e.g. `serdde`)
+        if normalized.ends_with(c) && c != '-' {
+            continue;
+        }
+        normalized.push(c);
+    }
+    // (`rust-` and `-rs` are not stripped: `encoding` and `encoding_rs` are different crates)
+    normalized
+}
+
+/// returns the popular crate that this crate might be impersonating, if any
+pub fn get_typosquatting_suspect(crate_name: &str) -> Option<String> {
+    let popular_crates = POPULAR_CRATES
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+    // popular crates are not typosquatting themselves
+    if popular_crates.clone().any(|popular| popular == crate_name) {
+        return None;
+    }
+
+    let normalized_name = normalize_crate_name(crate_name);
+    for popular in popular_crates {
+        // common substitutions (`-` vs `_`, `0` vs `o`, etc.)
+        if normalize_crate_name(popular) == normalized_name {
+            return Some(popular.to_string());
+        }
+        // small typo on a name long enough to not be a coincidence
+        if popular.len() >= 5 && strsim::damerau_levenshtein(popular, crate_name) == 1 {
+            return Some(popular.to_string());
+        }
+    }
+
+    //
+    None
+}
+
+/// parses a Cargo.lock and returns the checksum of every (crate, version) it lists
+pub fn parse_lockfile_checksums(lockfile_path: &Path) -> HashMap<(String, String), String> {
     #[derive(Deserialize, Debug)]
-    struct Crate {
-        updated_at: String,
+    struct Package {
+        name: String,
+        version: String,
+        checksum: Option<String>,
     }
     #[derive(Deserialize, Debug)]
-    pub struct Response {
-        #[serde(rename = "crate")]
-        crate_: Crate,
+    struct Lockfile {
+        #[serde(default)]
+        package: Vec<Package>,
+        // older lockfiles store checksums as `"checksum <name> <version> (<source>)" = "<checksum>"`
+        #[serde(default)]
+        metadata: HashMap<String, String>,
     }
-    // create request to crates.io API
-    let request_url = format!("https://crates.io/api/v1/crates/{}", crate_name,);
-    let request = http_client.get(&request_url);
-    // send the request
-    let resp = match request.send() {
+    let mut checksums = HashMap::new();
+    let lockfile = match fs::read_to_string(lockfile_path)
+        .map_err(|err| err.to_string())
+        .and_then(|contents| toml::from_str::<Lockfile>(&contents).map_err(|err| err.to_string()))
+    {
+        Ok(lockfile) => lockfile,
         Err(err) => {
-            eprintln!("{}", err);
-            return None;
+            eprintln!("dephell: couldn't parse {:?}: {}", lockfile_path, err);
+            return checksums;
         }
-        Ok(resp) => resp,
     };
-    // parse response
-    if !resp.status().is_success() {
-        eprintln!("dephell: crates.io request failed");
-        eprintln!("status: {}", resp.status());
-        eprintln!("text: {:?}", resp.text());
-        return None;
+    for package in lockfile.package {
+        if let Some(checksum) = package.checksum {
+            checksums.insert((package.name, package.version), checksum);
+        }
     }
-    let resp: reqwest::Result<Response> = resp.json();
-    match resp {
+    for (key, checksum) in lockfile.metadata {
+        let mut key = key.split_whitespace();
+        if let (Some("checksum"), Some(name), Some(version)) = (key.next(), key.next(), key.next())
+        {
+            checksums.insert((name.to_string(), version.to_string()), checksum);
+        }
+    }
+    checksums
+}
+
+/// reads the `rust-version` (MSRV) and `edition` of a package from its Cargo.toml,
+/// the edition defaults to 2015 when it isn't specified
+pub fn get_toolchain_requirements(manifest_path: &Path) -> (Option<String>, String) {
+    let manifest = fs::read_to_string(manifest_path)
+        .ok()
+        .and_then(|contents| contents.parse::<toml::Value>().ok());
+    let package = manifest
+        .as_ref()
+        .and_then(|manifest| manifest.get("package"));
+    // inherited values (`rust-version.workspace = true`) aren't strings, and are ignored
+    let field = |name| {
+        package
+            .and_then(|package| package.get(name))
+            .and_then(|value| value.as_str())
+            .map(|value| value.to_owned())
+    };
+    let edition = field("edition").unwrap_or_else(|| "2015".to_owned());
+    (field("rust-version"), edition)
+}
+
+/// finds the .crate file of a dependency in the cargo registry cache
+fn find_cached_crate(crate_name: &str, version: &str) -> Option<PathBuf> {
+    let cargo_home = std::env::var("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| std::env::var("HOME").map(|home| PathBuf::from(home).join(".cargo")))
+        .ok()?;
+    let mut cached_crate = cargo_home;
+    cached_crate.push("registry/cache/*");
+    cached_crate.push(format!("{}-{}.crate", crate_name, version));
+    glob::glob(cached_crate.to_str()?)
+        .ok()?
+        .filter_map(|path| path.ok())
+        .next()
+}
+
+/// returns the size (in bytes) and the number of files of the .crate in the cargo registry cache
+pub fn get_crate_archive_stats(crate_name: &str, version: &str) -> Option<(u64, u64)> {
+    let cached_crate = find_cached_crate(crate_name, version)?;
+    let size = fs::metadata(&cached_crate).ok()?.len();
+    let file = match fs::File::open(&cached_crate) {
+        Ok(file) => file,
         Err(err) => {
-            eprintln!("dephell: {}", err);
-            None
+            eprintln!("dephell: couldn't read {:?}: {}", cached_crate, err);
+            return None;
         }
-        Ok(resp) => {
-            let updated_at = resp.crate_.updated_at;
-            let formatted_date = chrono::DateTime::parse_from_rfc3339(&updated_at)
-                .unwrap()
-                .format("%Y-%m-%d")
-                .to_string();
-            Some(formatted_date)
+    };
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(file));
+    let file_count = archive
+        .entries()
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.header().entry_type().is_file())
+        .count();
+    Some((size, file_count as u64))
+}
+
+/// checks that the .crate in the cargo registry cache matches the checksum recorded in Cargo.lock,
+/// returns None if there's nothing to verify
+pub fn verify_checksum(crate_name: &str, version: &str, expected_checksum: &str) -> Option<bool> {
+    use sha2::{Digest, Sha256};
+    let cached_crate = find_cached_crate(crate_name, version)?;
+    let contents = match fs::read(&cached_crate) {
+        Ok(contents) => contents,
+        Err(err) => {
+            eprintln!("dephell: couldn't read {:?}: {}", cached_crate, err);
+            return None;
+        }
+    };
+    let checksum = format!("{:x}", Sha256::digest(&contents));
+    Some(checksum == expected_checksum)
+}
+
+/// obtains the kinds of edges ("normal", "build", "dev") through which the root crates import
+/// each package. Note that anything imported by a build dependency is a build dependency,
+/// and anything imported by a dev dependency is a dev dependency.
+pub fn get_dependency_kinds(
+    package_graph: &PackageGraph,
+    root_crates: &HashSet<PackageId>,
+) -> HashMap<PackageId, BTreeSet<String>> {
+    let mut dependency_kinds: HashMap<PackageId, BTreeSet<String>> = HashMap::new();
+
+    // root crate > direct dependency
+    let mut to_visit: Vec<(&PackageId, &str)> = Vec::new();
+    for root_crate in root_crates {
+        for dep_link in package_graph.metadata(root_crate).unwrap().direct_links() {
+            if dep_link.normal().is_present() {
+                to_visit.push((dep_link.to().id(), "normal"));
+            }
+            if dep_link.build().is_present() {
+                to_visit.push((dep_link.to().id(), "build"));
+            }
+            if dep_link.dev().is_present() {
+                to_visit.push((dep_link.to().id(), "dev"));
+            }
+        }
+    }
+
+    // direct dependency > transitive dependencies
+    while let Some((package_id, kind)) = to_visit.pop() {
+        let kinds = dependency_kinds.entry(package_id.clone()).or_default();
+        if !kinds.insert(kind.to_string()) {
+            continue; // already visited
+        }
+        for dep_link in package_graph.metadata(package_id).unwrap().direct_links() {
+            if dep_link.normal().is_present() {
+                to_visit.push((dep_link.to().id(), kind));
+            }
+            if dep_link.build().is_present() {
+                let kind = if kind == "dev" { "dev" } else { "build" };
+                to_visit.push((dep_link.to().id(), kind));
+            }
         }
     }
+
+    //
+    dependency_kinds
 }
 
 /// obtains all root crates that end up importing this dependency
@@ -235,11 +1962,66 @@ pub fn get_root_importers(
         .collect();
     let root_importers = root_importers
         .iter()
-        .map(|pkg_metadata| pkg_metadata.name().to_string())
+        .map(|pkg_metadata| package_key(pkg_metadata.name(), pkg_metadata.version()))
         .collect();
     root_importers
 }
 
+/// obtains the shortest chains of imports from the root crates to this dependency, shortest first
+/// (one per root crate importing it), e.g. `["root@0.1.0", "reqwest@0.10.4", "dependency@1.0.0"]`
+pub fn get_import_paths(
+    package_graph: &PackageGraph,
+    root_crates: &HashSet<PackageId>,
+    dependency: &PackageId,
+) -> Vec<Vec<String>> {
+    // the importers of every package leading to the dependency
+    let links = package_graph
+        .query_reverse(std::iter::once(dependency))
+        .unwrap();
+    let links = links.resolve();
+    let mut importers: HashMap<PackageId, Vec<PackageId>> = HashMap::new();
+    for link in links.links(DependencyDirection::Reverse) {
+        importers
+            .entry(link.to().id().clone())
+            .or_default()
+            .push(link.from().id().clone());
+    }
+
+    // breadth-first search from the dependency up to the root crates,
+    // so that the first chain found to a root crate is the shortest
+    let key = |package_id: &PackageId| {
+        let package_metadata = package_graph.metadata(package_id).unwrap();
+        package_key(package_metadata.name(), package_metadata.version())
+    };
+    let mut imported: HashMap<PackageId, PackageId> = HashMap::new(); // importer -> package it imports
+    let mut visited: HashSet<PackageId> = HashSet::new();
+    let mut queue = VecDeque::new();
+    visited.insert(dependency.clone());
+    queue.push_back(dependency.clone());
+    let mut import_paths = Vec::new();
+    while let Some(package_id) = queue.pop_front() {
+        if &package_id != dependency && root_crates.contains(&package_id) {
+            // follow the chain back down to the dependency
+            let mut import_path = vec![key(&package_id)];
+            let mut current = &package_id;
+            while let Some(next) = imported.get(current) {
+                import_path.push(key(next));
+                current = next;
+            }
+            import_paths.push(import_path);
+        }
+        for importer in importers.get(&package_id).into_iter().flatten() {
+            if visited.insert(importer.clone()) {
+                imported.insert(importer.clone(), package_id.clone());
+                queue.push_back(importer.clone());
+            }
+        }
+    }
+
+    //
+    import_paths
+}
+
 /// obtains all the dependencies that are introduced by this dependency, and this dependency only
 pub fn get_exclusive_deps(
     package_graph: &PackageGraph,
@@ -276,7 +2058,7 @@ pub fn get_exclusive_deps(
         }
         // if it's not in the new graph, it's exclusive to our dependency!
         if !new_all.contains(&transitive_dep.id()) {
-            exclusive_deps.push(transitive_dep.name().to_string());
+            exclusive_deps.push(package_key(transitive_dep.name(), transitive_dep.version()));
         }
     }
 
@@ -301,6 +2083,317 @@ pub fn get_loc(package_risk: &mut PackageRisk, dependency_files: &HashSet<String
     }
 }
 
+/// UnsafeVisitor counts the uses of `unsafe` in a rust file
+#[derive(Default)]
+struct UnsafeVisitor {
+    /// the file being analyzed (relative to the package)
+    file: String,
+    usage: UnsafeUsage,
+    /// number of lines in unsafe blocks or unsafe functions
+    unsafe_loc: u64,
+    /// where the unsafe code is
+    locations: Vec<UnsafeLocation>,
+    /// lines where unsafe blocks start
+    block_lines: Vec<usize>,
+    /// are we already in an unsafe context? (to not count nested unsafe twice)
+    in_unsafe: bool,
+}
+
+impl UnsafeVisitor {
+    /// records the location of some unsafe code
+    fn record(&mut self, span: proc_macro2::Span, kind: &str) {
+        self.locations.push(UnsafeLocation {
+            file: self.file.clone(),
+            line: span.start().line,
+            span: span.end().line - span.start().line + 1,
+            kind: kind.to_string(),
+        });
+    }
+
+    /// counts the lines of an unsafe context and visits it
+    fn visit_unsafe_context<F>(&mut self, span: proc_macro2::Span, kind: &str, visit_inner: F)
+    where
+        F: FnOnce(&mut Self),
+    {
+        if self.in_unsafe {
+            visit_inner(self);
+            return;
+        }
+        self.unsafe_loc += (span.end().line - span.start().line + 1) as u64;
+        self.record(span, kind);
+        self.in_unsafe = true;
+        visit_inner(self);
+        self.in_unsafe = false;
+    }
+
+    /// counts the unsafe code in the body of a macro (`macro_rules!` or macro invocation),
+    /// which the parser leaves as raw tokens
+    fn visit_macro_tokens(&mut self, tokens: proc_macro2::TokenStream) {
+        use proc_macro2::{Delimiter, TokenTree};
+        let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+        // the first brace group after `idx`, unless the item ends before (e.g. `unsafe fn f();`)
+        let body_after = |idx: usize| {
+            for (body_idx, token) in tokens.iter().enumerate().skip(idx + 1) {
+                match token {
+                    TokenTree::Group(group) if group.delimiter() == Delimiter::Brace => {
+                        return Some(body_idx)
+                    }
+                    TokenTree::Punct(punct) if punct.as_char() == ';' => return None,
+                    _ => (),
+                }
+            }
+            None
+        };
+
+        // the brace groups that are the bodies of unsafe blocks or unsafe functions
+        let mut unsafe_bodies = HashSet::new();
+        for (idx, token) in tokens.iter().enumerate() {
+            let ident = match token {
+                TokenTree::Ident(ident) if ident == "unsafe" => ident,
+                _ => continue,
+            };
+            match tokens.get(idx + 1) {
+                Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => {
+                    self.usage.blocks += 1;
+                    self.block_lines.push(ident.span().start().line);
+                    unsafe_bodies.insert((idx + 1, "block"));
+                }
+                Some(TokenTree::Ident(next)) if next == "impl" => {
+                    self.usage.impls += 1;
+                    self.record(next.span(), "impl");
+                }
+                Some(TokenTree::Ident(next)) if next == "trait" => {
+                    self.usage.traits += 1;
+                    self.record(next.span(), "trait");
+                }
+                Some(TokenTree::Ident(next)) if next == "fn" || next == "extern" => {
+                    self.usage.functions += 1;
+                    if let Some(body_idx) = body_after(idx) {
+                        unsafe_bodies.insert((body_idx, "function"));
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        // visit the nested groups, counting the lines of the unsafe ones
+        for (idx, token) in tokens.iter().enumerate() {
+            let group = match token {
+                TokenTree::Group(group) => group,
+                _ => continue,
+            };
+            let kind = ["block", "function"]
+                .iter()
+                .find(|kind| unsafe_bodies.contains(&(idx, **kind)));
+            match kind {
+                Some(kind) => self.visit_unsafe_context(group.span(), kind, |visitor| {
+                    visitor.visit_macro_tokens(group.stream())
+                }),
+                None => self.visit_macro_tokens(group.stream()),
+            }
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for UnsafeVisitor {
+    fn visit_expr_unsafe(&mut self, expr: &'ast syn::ExprUnsafe) {
+        self.usage.blocks += 1;
+        self.block_lines.push(expr.unsafe_token.span.start().line);
+        let span = expr.block.brace_token.span;
+        self.visit_unsafe_context(span, "block", |visitor| {
+            visit::visit_expr_unsafe(visitor, expr)
+        });
+    }
+
+    fn visit_item_fn(&mut self, item_fn: &'ast syn::ItemFn) {
+        if scan::is_cfg_test(&item_fn.attrs) {
+            return;
+        }
+        if item_fn.sig.unsafety.is_none() {
+            visit::visit_item_fn(self, item_fn);
+            return;
+        }
+        self.usage.functions += 1;
+        let span = item_fn.block.brace_token.span;
+        self.visit_unsafe_context(span, "function", |visitor| {
+            visit::visit_item_fn(visitor, item_fn)
+        });
+    }
+
+    fn visit_impl_item_method(&mut self, method: &'ast syn::ImplItemMethod) {
+        if scan::is_cfg_test(&method.attrs) {
+            return;
+        }
+        if method.sig.unsafety.is_none() {
+            visit::visit_impl_item_method(self, method);
+            return;
+        }
+        self.usage.functions += 1;
+        let span = method.block.brace_token.span;
+        self.visit_unsafe_context(span, "function", |visitor| {
+            visit::visit_impl_item_method(visitor, method)
+        });
+    }
+
+    fn visit_trait_item_method(&mut self, method: &'ast syn::TraitItemMethod) {
+        if method.sig.unsafety.is_some() {
+            self.usage.functions += 1;
+        }
+        visit::visit_trait_item_method(self, method);
+    }
+
+    fn visit_item_impl(&mut self, item_impl: &'ast syn::ItemImpl) {
+        if scan::is_cfg_test(&item_impl.attrs) {
+            return;
+        }
+        if item_impl.unsafety.is_some() {
+            self.usage.impls += 1;
+            self.record(item_impl.impl_token.span, "impl");
+            if let Some((_, trait_path, _)) = &item_impl.trait_ {
+                let trait_name = trait_path.segments.last().map(|s| s.ident.to_string());
+                if trait_name.as_deref() == Some("Send") || trait_name.as_deref() == Some("Sync") {
+                    self.usage.send_sync_impls += 1;
+                }
+            }
+        }
+        visit::visit_item_impl(self, item_impl);
+    }
+
+    fn visit_item_static(&mut self, item_static: &'ast syn::ItemStatic) {
+        if scan::is_cfg_test(&item_static.attrs) {
+            return;
+        }
+        if item_static.mutability.is_some() {
+            self.usage.static_muts += 1;
+        }
+        visit::visit_item_static(self, item_static);
+    }
+
+    fn visit_item_foreign_mod(&mut self, foreign_mod: &'ast syn::ItemForeignMod) {
+        if scan::is_cfg_test(&foreign_mod.attrs) {
+            return;
+        }
+        self.usage.extern_blocks += 1;
+        self.record(foreign_mod.brace_token.span, "extern");
+        visit::visit_item_foreign_mod(self, foreign_mod);
+    }
+
+    fn visit_expr_unary(&mut self, expr: &'ast syn::ExprUnary) {
+        // we can't know the type of what's dereferenced, but in unsafe code it's likely a raw pointer
+        if self.in_unsafe {
+            if let syn::UnOp::Deref(_) = expr.op {
+                self.usage.raw_pointer_derefs += 1;
+            }
+        }
+        visit::visit_expr_unary(self, expr);
+    }
+
+    fn visit_path(&mut self, path: &'ast syn::Path) {
+        let segments: Vec<String> = path
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect();
+        let segments: Vec<&str> = segments.iter().map(|s| s.as_str()).collect();
+        match segments.as_slice() {
+            [.., "transmute"] | [.., "transmute_copy"] => self.usage.transmutes += 1,
+            [.., "ptr", "read"]
+            | [.., "ptr", "read_unaligned"]
+            | [.., "ptr", "read_volatile"]
+            | [.., "ptr", "write"]
+            | [.., "ptr", "write_unaligned"]
+            | [.., "ptr", "write_volatile"] => self.usage.raw_pointer_derefs += 1,
+            _ => (),
+        }
+        visit::visit_path(self, path);
+    }
+
+    fn visit_item_trait(&mut self, item_trait: &'ast syn::ItemTrait) {
+        if scan::is_cfg_test(&item_trait.attrs) {
+            return;
+        }
+        if item_trait.unsafety.is_some() {
+            self.usage.traits += 1;
+            self.record(item_trait.trait_token.span, "trait");
+        }
+        visit::visit_item_trait(self, item_trait);
+    }
+
+    fn visit_item_macro(&mut self, item_macro: &'ast syn::ItemMacro) {
+        if !scan::is_cfg_test(&item_macro.attrs) {
+            visit::visit_item_macro(self, item_macro);
+        }
+    }
+
+    // the bodies of macros are not parsed, so we look for `unsafe` in their tokens
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        self.visit_macro_tokens(mac.tokens.clone());
+        visit::visit_macro(self, mac);
+    }
+
+    // skip tests
+    fn visit_item_mod(&mut self, item_mod: &'ast syn::ItemMod) {
+        if !scan::is_cfg_test(&item_mod.attrs) {
+            visit::visit_item_mod(self, item_mod);
+        }
+    }
+}
+
+/// checks if the code at `line` (1-indexed) is documented with a `// SAFETY:` comment,
+/// either on the same line or in the comments right above it
+fn has_safety_comment(lines: &[&str], line: usize) -> bool {
+    let is_safety_comment = |line: &str| line.to_uppercase().contains("SAFETY");
+    if line == 0 || line > lines.len() {
+        return false;
+    }
+    if lines[line - 1].contains("//") && is_safety_comment(lines[line - 1]) {
+        return true;
+    }
+    for previous_line in lines[..line - 1].iter().rev() {
+        let previous_line = previous_line.trim();
+        let is_comment = previous_line.starts_with("//")
+            || previous_line.starts_with("/*")
+            || previous_line.starts_with('*');
+        // attributes can sit between the comment and the code
+        if !is_comment && !previous_line.starts_with("#[") {
+            return false;
+        }
+        if is_comment && is_safety_comment(previous_line) {
+            return true;
+        }
+    }
+    false
+}
+
+/// counts the unsafe code of all the given files
+/// (comments and strings are ignored by the parser, tests are skipped)
+pub fn get_unsafe(package_risk: &mut PackageRisk, rust_files: &[RustFile]) {
+    for rust_file in rust_files {
+        let mut visitor = UnsafeVisitor {
+            file: rust_file.path.clone(),
+            ..Default::default()
+        };
+        visitor.visit_file(&rust_file.ast);
+        package_risk.unsafe_loc += visitor.unsafe_loc;
+        package_risk.unsafe_locations.extend(visitor.locations);
+        package_risk.unsafe_usage.blocks += visitor.usage.blocks;
+        let lines: Vec<&str> = rust_file.contents.lines().collect();
+        package_risk.unsafe_usage.documented_blocks += visitor
+            .block_lines
+            .iter()
+            .filter(|line| has_safety_comment(&lines, **line))
+            .count() as u64;
+        package_risk.unsafe_usage.functions += visitor.usage.functions;
+        package_risk.unsafe_usage.impls += visitor.usage.impls;
+        package_risk.unsafe_usage.traits += visitor.usage.traits;
+        package_risk.unsafe_usage.transmutes += visitor.usage.transmutes;
+        package_risk.unsafe_usage.static_muts += visitor.usage.static_muts;
+        package_risk.unsafe_usage.raw_pointer_derefs += visitor.usage.raw_pointer_derefs;
+        package_risk.unsafe_usage.send_sync_impls += visitor.usage.send_sync_impls;
+        package_risk.unsafe_usage.extern_blocks += visitor.usage.extern_blocks;
+    }
+}
+
 /// parses the dep-info files that contain all the files relevant to the compilation of a dependency (these files are like Makefiles)
 // TODO: what to do about libraries linked via bindings
 fn parse_rustc_dep_info(rustc_dep_info: &Path) -> HashSet<String> {
@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+//
+// Reviewer notes
+// ==============
+// the notes of the people reviewing the dependencies, by crate name, e.g.
+//
+// ```toml
+// [openssl-sys]
+// status = "approved"
+// note = "links the system openssl, reviewed the build script"
+// ```
+//
+
+/// where the notes are looked for by default (next to Cargo.toml)
+pub const NOTES_FILE: &str = "dephell-notes.toml";
+
+/// where the review of a crate is at
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewStatus {
+    Approved,
+    Pending,
+    Rejected,
+}
+
+/// what reviewers wrote about a crate
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReviewerNotes {
+    pub status: Option<ReviewStatus>,
+    #[serde(default)]
+    pub note: String,
+}
+
+/// parses a `dephell-notes.toml` and returns the notes about every crate
+pub fn parse_notes(notes_path: &Path) -> Result<HashMap<String, ReviewerNotes>, String> {
+    let contents = fs::read_to_string(notes_path)
+        .map_err(|err| format!("dephell: could not read {}: {}", notes_path.display(), err))?;
+    toml::from_str(&contents)
+        .map_err(|err| format!("dephell: could not parse {}: {}", notes_path.display(), err))
+}
@@ -0,0 +1,963 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use askama::Template;
+
+use crate::analysis::PackageRisk;
+use crate::{findings, report, HistoryPoint, JsonReport, JsonResult, RunMetadata};
+
+//
+// Output
+// ======
+// the formats the result of an analysis can be written in,
+// to stdout or to a file
+//
+
+/// Format is a format the result can be written in
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    Json,
+    Jsonl,
+    Html,
+    Csv,
+    Markdown,
+    Junit,
+    Gitlab,
+    OpenMetrics,
+    Dot,
+    Sarif,
+    CycloneDx,
+    Spdx,
+    SpdxTagValue,
+    Badge,
+    Shields,
+    Table,
+    Inventory,
+    Template,
+}
+
+/// names accepted by `--format` (`md` is short for `markdown`)
+pub const FORMATS: &[&str] = &[
+    "json",
+    "jsonl",
+    "html",
+    "csv",
+    "markdown",
+    "md",
+    "junit",
+    "gitlab",
+    "openmetrics",
+    "dot",
+    "sarif",
+    "cyclonedx",
+    "spdx",
+    "spdx-tv",
+    "badge",
+    "shields",
+    "table",
+    "inventory",
+    "template",
+];
+
+impl Format {
+    /// parses a name from `FORMATS`
+    pub fn from_name(name: &str) -> Option<Self> {
+        let format = match name {
+            "json" => Format::Json,
+            "jsonl" => Format::Jsonl,
+            "html" => Format::Html,
+            "csv" => Format::Csv,
+            "markdown" | "md" => Format::Markdown,
+            "junit" => Format::Junit,
+            "gitlab" => Format::Gitlab,
+            "openmetrics" => Format::OpenMetrics,
+            "dot" => Format::Dot,
+            "sarif" => Format::Sarif,
+            "cyclonedx" => Format::CycloneDx,
+            "spdx" => Format::Spdx,
+            "spdx-tv" => Format::SpdxTagValue,
+            "badge" => Format::Badge,
+            "shields" => Format::Shields,
+            "table" => Format::Table,
+            "inventory" => Format::Inventory,
+            "template" => Format::Template,
+            _ => return None,
+        };
+        Some(format)
+    }
+
+    /// guesses the format from the extension of an output file
+    pub fn from_extension(path: &str) -> Option<Self> {
+        let extension = Path::new(path).extension()?.to_str()?.to_lowercase();
+        let format = match extension.as_str() {
+            "json" => Format::Json,
+            "jsonl" | "ndjson" => Format::Jsonl,
+            "html" | "htm" => Format::Html,
+            "csv" => Format::Csv,
+            "md" => Format::Markdown,
+            "dot" | "gv" => Format::Dot,
+            "sarif" => Format::Sarif,
+            "spdx" => Format::SpdxTagValue,
+            "svg" => Format::Badge,
+            _ => return None,
+        };
+        Some(format)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Jsonl => "jsonl",
+            Format::Html => "html",
+            Format::Csv => "csv",
+            Format::Markdown => "markdown",
+            Format::Junit => "junit",
+            Format::Gitlab => "gitlab",
+            Format::OpenMetrics => "openmetrics",
+            Format::Dot => "dot",
+            Format::Sarif => "sarif",
+            Format::CycloneDx => "cyclonedx",
+            Format::Spdx => "spdx",
+            Format::SpdxTagValue => "spdx-tv",
+            Format::Badge => "badge",
+            Format::Shields => "shields",
+            Format::Table => "table",
+            Format::Inventory => "inventory",
+            Format::Template => "template",
+        }
+    }
+}
+
+/// Context is everything a format can be rendered from
+pub struct Context<'a> {
+    /// name of the project (the directory containing the manifest)
+    pub name: &'a str,
+    pub manifest_path: &'a Path,
+    pub report: &'a JsonReport,
+    /// a previous analysis, to show what changed since
+    pub baseline: Option<&'a JsonResult>,
+    /// the totals of previous analyses, oldest first (to chart the trends)
+    pub history: &'a [HistoryPoint],
+    /// when the analysis started and ended (or `$SOURCE_DATE_EPOCH`, for reproducible outputs)
+    pub start_time: chrono::DateTime<chrono::Utc>,
+    pub end_time: chrono::DateTime<chrono::Utc>,
+    /// pretty-prints JSON
+    pub pretty: bool,
+    /// what the badges show
+    pub badge_metric: report::BadgeMetric,
+    /// colors the table
+    pub color: bool,
+    /// outputs HTML for printing instead of the interactive page
+    pub printable: bool,
+    /// look of the HTML output
+    pub theme: Theme<'a>,
+    /// columns of the HTML table shown by default (all of them if none)
+    pub columns: Option<&'a [&'a str]>,
+    /// risk scores from which a dependency is graded B, C, D and F in the HTML output
+    pub grade_thresholds: [u64; 4],
+    /// user template to render the result with (`--template`)
+    pub template: Option<&'a Path>,
+    /// the scripts and stylesheets to inline in the HTML output (`--offline-html`)
+    pub html_assets: Option<&'a HtmlAssets>,
+}
+
+/// parses the value of `--grade-thresholds`: four increasing risk scores
+pub fn parse_grade_thresholds(value: &str) -> Result<[u64; 4], String> {
+    let thresholds = value
+        .split(',')
+        .map(|threshold| threshold.trim().parse::<u64>())
+        .collect::<Result<Vec<u64>, _>>()
+        .map_err(|err| err.to_string())?;
+    if thresholds.len() != 4 || thresholds.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err("expected four increasing risk scores (e.g. 10,25,50,80)".to_owned());
+    }
+    Ok([thresholds[0], thresholds[1], thresholds[2], thresholds[3]])
+}
+
+/// columns of the HTML table that can be hidden (the name is always shown),
+/// named after the fields of the JSON result
+pub const COLUMNS: &[&str] = &[
+    "risk_score",
+    "dependency_kinds",
+    "transitive_dependencies",
+    "versions",
+    "root_importers",
+    "exclusive_deps_introduced",
+    "loc",
+    "rust_loc",
+    "unsafe_loc",
+    "known_advisories",
+    "capabilities",
+    "crate_size",
+    "binary_artifacts",
+    "stargazers_count",
+    "last_commit",
+    "active_contributors",
+    "contributors",
+    "bus_factor",
+    "open_issues",
+    "median_response_time",
+    "security_policy",
+    "sourcerank",
+    "crates_io_dependent",
+    "major_versions_behind",
+    "stale_own_deps",
+    "rust_version",
+    "latest_release",
+    "release_interval",
+    "downloads_total",
+    "downloads_recent",
+    "crates_io_last_updated",
+    "reviewer_notes",
+];
+
+/// themes accepted by `--theme` (`auto` follows the preference of the browser)
+pub const THEMES: &[&str] = &["auto", "light", "dark"];
+
+/// Theme is the look of the HTML output,
+/// readers can still switch between the light and dark themes on the page
+#[derive(Clone, Copy)]
+pub struct Theme<'a> {
+    /// one of `THEMES`
+    pub default: &'a str,
+    /// color of the header
+    pub brand_color: &'a str,
+    /// color of the table headers
+    pub accent_color: &'a str,
+}
+
+/// HtmlAssets are the scripts and stylesheets the HTML output pulls from CDNs,
+/// they are inlined in the page with `--offline-html` (to view it on an air-gapped machine)
+pub struct HtmlAssets {
+    /// the icons (as SVG, so that the webfonts of the stylesheet are not needed)
+    pub fontawesome_js: String,
+    pub tippy_css: String,
+    pub popper_js: String,
+    pub tippy_js: String,
+    pub pako_js: String,
+    pub d3_js: String,
+}
+
+/// the scripts and stylesheets of the HTML output, vendored in `data/vendor/` at exact versions
+/// (see `data/vendor/update.sh`), so that generating an offline report doesn't need the network
+const FONTAWESOME_JS: &str = include_str!("../data/vendor/fontawesome-5.12.1-all.min.js");
+const TIPPY_CSS: &str = include_str!("../data/vendor/tippy-6.3.7-shift-away.css");
+const POPPER_JS: &str = include_str!("../data/vendor/popper-2.11.8.min.js");
+const TIPPY_JS: &str = include_str!("../data/vendor/tippy-6.3.7-bundle.umd.min.js");
+const PAKO_JS: &str = include_str!("../data/vendor/pako-2.1.0.min.js");
+const D3_JS: &str = include_str!("../data/vendor/d3-7.9.0.min.js");
+
+/// the vendored scripts and stylesheets of the HTML output, to inline them in the page
+pub fn html_assets() -> HtmlAssets {
+    // the assets can't close the tag they're inlined in
+    let inline = |asset: &str| {
+        asset
+            .replace("</script", "<\\/script")
+            .replace("</style", "<\\/style")
+    };
+    HtmlAssets {
+        fontawesome_js: inline(FONTAWESOME_JS),
+        tippy_css: inline(TIPPY_CSS),
+        popper_js: inline(POPPER_JS),
+        tippy_js: inline(TIPPY_JS),
+        pako_js: inline(PAKO_JS),
+        d3_js: inline(D3_JS),
+    }
+}
+
+/// checks that a color is a hex color (`#be4141`) or a color name,
+/// as it is written as is in the stylesheet of the HTML output
+pub fn is_color(color: &str) -> bool {
+    if color.starts_with('#') {
+        let hex = &color[1..];
+        [3, 4, 6, 8].contains(&hex.len()) && hex.chars().all(|c| c.is_ascii_hexdigit())
+    } else {
+        !color.is_empty() && color.chars().all(|c| c.is_ascii_alphabetic())
+    }
+}
+
+/// parses the value of `--output`: `FORMAT=PATH`, or just `PATH`
+pub fn parse_output(value: &str) -> (Option<Format>, &str) {
+    let mut format_path = value.splitn(2, '=');
+    if let (Some(format), Some(path)) = (format_path.next(), format_path.next()) {
+        if let Some(format) = Format::from_name(format) {
+            return (Some(format), path);
+        }
+    }
+    (None, value)
+}
+
+/// opens where an output goes: the file at `path`, or stdout
+pub fn create(path: Option<&str>) -> io::Result<Box<dyn Write>> {
+    match path {
+        Some(path) => Ok(Box::new(File::create(path)?)),
+        None => Ok(Box::new(io::stdout())),
+    }
+}
+
+/// renders the result in a format
+/// (for JSON lines, only the last line: the packages are streamed during the analysis)
+pub fn render(format: Format, context: &Context) -> Result<String, String> {
+    let JsonResult {
+        root_crates,
+        main_dependencies,
+        analysis_result,
+        ..
+    } = &context.report.result;
+    let totals = context.report.result.totals();
+    let get_findings = || findings::get_findings(main_dependencies, analysis_result);
+    let rendered = match format {
+        Format::Json if context.pretty => serde_json::to_string_pretty(context.report).unwrap(),
+        Format::Json => serde_json::to_string(context.report).unwrap(),
+        Format::Jsonl => {
+            // what depends on the whole tree comes last
+            let aggregates: BTreeMap<&String, serde_json::Value> = analysis_result
+                .iter()
+                .map(|(package_id, package_risk)| {
+                    let aggregate = serde_json::json!({
+                        "total_loc": package_risk.total_loc,
+                        "total_rust_loc": package_risk.total_rust_loc,
+                        "total_unsafe_loc": package_risk.total_unsafe_loc,
+                        "stale_own_deps": package_risk.stale_own_deps,
+                        "known_advisories": package_risk.known_advisories,
+                    });
+                    (package_id, aggregate)
+                })
+                .collect();
+            let summary = serde_json::json!({
+                "root_crates": root_crates,
+                "main_dependencies": main_dependencies,
+                "totals": totals,
+                "aggregates": aggregates,
+            });
+            summary.to_string()
+        }
+        Format::Html if context.printable => {
+            let root_crates: Vec<&str> = root_crates.iter().map(|root| root.as_str()).collect();
+            let html_page = HtmlPrintable {
+                name: context.name,
+                root_crates: root_crates.join(", "),
+                metadata: &context.report.metadata,
+                totals,
+                findings: get_findings(),
+                rows: site_rows(main_dependencies, analysis_result),
+            };
+            html_page.render().unwrap()
+        }
+        Format::Html => {
+            let json_result = serde_json::to_string(context.report).unwrap();
+            let baseline_result = context
+                .baseline
+                .map(|baseline| compress_json(&baseline_summary(baseline).to_string()))
+                .unwrap_or_default();
+            let html_page = HtmlList {
+                name: context.name.to_owned(),
+                json_result: compress_json(&json_result),
+                baseline_result,
+                theme: context.theme,
+                default_columns: serde_json::to_string(&context.columns).unwrap(),
+                grade_thresholds: serde_json::to_string(&context.grade_thresholds).unwrap(),
+                totals: serde_json::to_string(&totals).unwrap(),
+                assets: context.html_assets,
+                history: serde_json::to_string(context.history).unwrap(),
+            };
+            html_page.render().unwrap()
+        }
+        Format::Csv => report::to_csv(analysis_result),
+        Format::Inventory => report::to_inventory_csv(analysis_result),
+        Format::Markdown => {
+            let baseline = context
+                .baseline
+                .map(|baseline| (baseline.totals(), &baseline.analysis_result));
+            report::to_markdown(
+                context.name,
+                &totals,
+                analysis_result,
+                &get_findings(),
+                &context.report.result.policy_violations,
+                baseline.as_ref().map(|(totals, result)| (totals, *result)),
+            )
+        }
+        Format::Junit => report::to_junit(
+            analysis_result,
+            &get_findings(),
+            &context.report.result.policy_violations,
+        ),
+        Format::Gitlab => report::to_gitlab(
+            analysis_result,
+            &get_findings(),
+            &context.report.result.policy_violations,
+            context.start_time,
+            context.end_time,
+        )
+        .to_string(),
+        Format::OpenMetrics => report::to_openmetrics(&totals, analysis_result),
+        Format::Dot => report::to_dot(context.name, main_dependencies, analysis_result),
+        Format::Sarif => report::to_sarif(context.manifest_path, &get_findings()).to_string(),
+        Format::CycloneDx => report::to_cyclonedx(
+            context.name,
+            main_dependencies,
+            analysis_result,
+            context.start_time,
+        )
+        .to_string(),
+        Format::Spdx => report::to_spdx(
+            context.name,
+            main_dependencies,
+            analysis_result,
+            context.start_time,
+        )
+        .to_string(),
+        Format::SpdxTagValue => {
+            let spdx = report::to_spdx(
+                context.name,
+                main_dependencies,
+                analysis_result,
+                context.start_time,
+            );
+            report::to_spdx_tag_value(&spdx)
+        }
+        Format::Table => report::to_table(&totals, analysis_result, &get_findings(), context.color),
+        Format::Badge => report::to_badge_svg(context.badge_metric, &totals),
+        Format::Shields => report::to_shields_endpoint(context.badge_metric, &totals).to_string(),
+        Format::Template => return render_template(context),
+    };
+    Ok(rendered)
+}
+
+/// renders the result with the user template given with `--template` (a Tera template),
+/// which gets the JSON report (`schema_version`, `metadata` and `result`),
+/// the `name` of the project and the `totals` of the dependency tree.
+/// Like Tera, HTML and XML templates are escaped.
+fn render_template(context: &Context) -> Result<String, String> {
+    let template_path = context
+        .template
+        .ok_or_else(|| "no template given (use --template PATH)".to_owned())?;
+    let template = std::fs::read_to_string(template_path)
+        .map_err(|err| format!("couldn't read {}: {}", template_path.display(), err))?;
+    let escape = match template_path.extension().and_then(|ext| ext.to_str()) {
+        Some("html") | Some("htm") | Some("xml") => true,
+        _ => false,
+    };
+
+    // tera's errors are nested
+    let describe = |err: tera::Error| {
+        let mut description = err.to_string();
+        let mut source = std::error::Error::source(&err);
+        while let Some(err) = source {
+            description.push_str(": ");
+            description.push_str(&err.to_string());
+            source = err.source();
+        }
+        description
+    };
+    let mut tera_context = tera::Context::from_serialize(context.report).map_err(describe)?;
+    tera_context.insert("name", context.name);
+    tera_context.insert("totals", &context.report.result.totals());
+    tera::Tera::one_off(&template, &tera_context, escape).map_err(describe)
+}
+
+//
+// HTML Stuff
+// ==========
+//
+
+#[derive(Template)]
+#[template(path = "list.html", escape = "none")]
+struct HtmlList<'a> {
+    name: String,
+    /// the JSON report, gzipped and base64-encoded to lighten the page
+    json_result: String,
+    /// what the page compares to in the baseline (compressed the same way), if there's one
+    baseline_result: String,
+    theme: Theme<'a>,
+    /// the columns shown by default, as a JSON array (or null for all of them)
+    default_columns: String,
+    /// as a JSON array
+    grade_thresholds: String,
+    /// the totals of the dependency tree, as JSON (older reports don't have them)
+    totals: String,
+    /// inlined instead of pulled from CDNs, if any
+    assets: Option<&'a HtmlAssets>,
+    /// the totals of the previous analyses, as a JSON array
+    history: String,
+}
+
+/// HtmlPrintable is a linear version of the HTML output, without javascript,
+/// with a page per section when printed (e.g. to archive it as a PDF)
+#[derive(Template)]
+#[template(path = "printable.html")]
+struct HtmlPrintable<'a> {
+    name: &'a str,
+    root_crates: String,
+    metadata: &'a RunMetadata,
+    totals: report::Totals,
+    findings: Vec<findings::Finding>,
+    rows: Vec<SiteRow<'a>>,
+}
+
+/// the metrics of the baseline that the HTML output shows the changes of
+fn baseline_summary(baseline: &JsonResult) -> serde_json::Value {
+    let packages: BTreeMap<&String, serde_json::Value> = baseline
+        .analysis_result
+        .iter()
+        .map(|(package_id, package_risk)| {
+            let package = serde_json::json!({
+                "name": package_risk.name,
+                "internal": package_risk.internal,
+                "loc": package_risk.loc,
+                "rust_loc": package_risk.rust_loc,
+                "unsafe_loc": package_risk.unsafe_loc,
+                "total_loc": package_risk.total_loc,
+                "total_rust_loc": package_risk.total_rust_loc,
+                "total_unsafe_loc": package_risk.total_unsafe_loc,
+                "advisories": package_risk.known_advisories.len(),
+            });
+            (package_id, package)
+        })
+        .collect();
+    serde_json::json!({
+        "totals": baseline.totals(),
+        "packages": packages,
+    })
+}
+
+/// gzips the JSON report and encodes it in base64, to embed it in the HTML output
+fn compress_json(json: &str) -> String {
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder
+        .write_all(json.as_bytes())
+        .expect("writing to a Vec can't fail");
+    let compressed = encoder.finish().expect("writing to a Vec can't fail");
+    base64::encode(compressed)
+}
+
+//
+// Static Site
+// ===========
+// an index page, and a page per package (in `packages/`)
+//
+
+#[derive(Template)]
+#[template(path = "site/index.html")]
+struct SiteIndex<'a> {
+    title: &'a str,
+    /// the icons, inlined with `--offline-html`
+    assets: Option<&'a HtmlAssets>,
+    /// path to the root of the site
+    root: &'a str,
+    name: &'a str,
+    root_crates: String,
+    metadata: &'a RunMetadata,
+    totals: report::Totals,
+    rows: Vec<SiteRow<'a>>,
+}
+
+/// SiteRow is a third-party dependency in the index (or in the printable report)
+struct SiteRow<'a> {
+    page: String,
+    package_risk: &'a PackageRisk,
+    direct: bool,
+    versions: String,
+    kinds: String,
+    license: String,
+    stars: String,
+    last_commit: String,
+}
+
+#[derive(Template)]
+#[template(path = "site/package.html")]
+struct SitePackage<'a> {
+    title: &'a str,
+    assets: Option<&'a HtmlAssets>,
+    root: &'a str,
+    name: &'a str,
+    package_id: &'a str,
+    package_risk: &'a PackageRisk,
+    findings: Vec<String>,
+    metrics: Vec<SiteMetric>,
+    tree: Vec<SiteTreeRow>,
+    /// directory of the source pages of the package (relative to its page)
+    source_dir: String,
+    /// the flagged files of the package that have a source page
+    sources: Vec<String>,
+}
+
+struct SiteMetric {
+    label: &'static str,
+    value: String,
+}
+
+/// SiteTreeRow is a package in the dependency tree of a package
+struct SiteTreeRow {
+    depth: usize,
+    package_id: String,
+    /// the page of the package (none for packages that are not listed)
+    page: Option<String>,
+    /// was the package (and its dependencies) already listed above?
+    repeated: bool,
+}
+
+/// SiteSource is a source file of a package, with its flagged lines highlighted
+#[derive(Template)]
+#[template(path = "site/source.html")]
+struct SiteSource<'a> {
+    title: String,
+    assets: Option<&'a HtmlAssets>,
+    root: String,
+    name: &'a str,
+    package_id: &'a str,
+    package_page: String,
+    file: &'a str,
+    /// why the whole file was flagged (if it looks obfuscated)
+    suspicious: Option<&'a str>,
+    lines: Vec<SiteSourceLine<'a>>,
+}
+
+struct SiteSourceLine<'a> {
+    number: usize,
+    text: &'a str,
+    /// why the line is highlighted ("unsafe", "ffi" or "network"), empty if it isn't
+    flag: &'static str,
+}
+
+/// name of the page of a package
+fn site_page(package_id: &str) -> String {
+    format!("{}.html", package_id.replace('@', "-"))
+}
+
+/// directory of the source pages of a package (next to its page)
+fn site_source_dir(package_id: &str) -> String {
+    package_id.replace('@', "-")
+}
+
+/// displays an optional value
+fn or_dash<T: ToString>(value: &Option<T>) -> String {
+    value
+        .as_ref()
+        .map(|value| value.to_string())
+        .unwrap_or_else(|| "-".to_owned())
+}
+
+/// lists the third-party dependencies
+fn site_rows<'a>(
+    main_dependencies: &BTreeSet<String>,
+    analysis_result: &'a BTreeMap<String, PackageRisk>,
+) -> Vec<SiteRow<'a>> {
+    analysis_result
+        .iter()
+        .filter(|(_, package_risk)| !package_risk.internal)
+        .map(|(package_id, package_risk)| {
+            let versions: Vec<&str> = package_risk.versions.iter().map(|v| v.as_str()).collect();
+            let kinds: Vec<&str> = package_risk
+                .dependency_kinds
+                .iter()
+                .map(|kind| kind.as_str())
+                .collect();
+            SiteRow {
+                page: site_page(package_id),
+                package_risk,
+                direct: main_dependencies.contains(package_id),
+                versions: versions.join(", "),
+                kinds: kinds.join(", "),
+                license: or_dash(&package_risk.license),
+                stars: or_dash(&package_risk.stargazers_count),
+                last_commit: or_dash(&package_risk.last_commit),
+            }
+        })
+        .collect()
+}
+
+/// flattens the dependency tree of a package (like `cargo tree`),
+/// packages already listed are not expanded again
+fn site_tree(
+    analysis_result: &BTreeMap<String, PackageRisk>,
+    package_id: &str,
+) -> Vec<SiteTreeRow> {
+    let mut tree = Vec::new();
+    let mut visited = BTreeSet::new();
+    let mut to_visit: Vec<(usize, &str)> = Vec::new();
+    if let Some(package_risk) = analysis_result.get(package_id) {
+        for dependency in package_risk.direct_dependencies.iter().rev() {
+            to_visit.push((0, dependency));
+        }
+    }
+    while let Some((depth, dependency)) = to_visit.pop() {
+        let repeated = !visited.insert(dependency);
+        let package_risk = analysis_result.get(dependency);
+        tree.push(SiteTreeRow {
+            depth,
+            package_id: dependency.to_owned(),
+            page: package_risk.map(|_| site_page(dependency)),
+            repeated,
+        });
+        if let (Some(package_risk), false) = (package_risk, repeated) {
+            for dependency in package_risk.direct_dependencies.iter().rev() {
+                to_visit.push((depth + 1, dependency));
+            }
+        }
+    }
+    tree
+}
+
+/// the main metrics of a package, to display on its page
+fn site_metrics(package_risk: &PackageRisk) -> Vec<SiteMetric> {
+    let join = |values: Vec<&String>| {
+        let values: Vec<&str> = values.iter().map(|value| value.as_str()).collect();
+        values.join(", ")
+    };
+    let metrics = vec![
+        ("versions", join(package_risk.versions.iter().collect())),
+        ("license", or_dash(&package_risk.license)),
+        ("edition", package_risk.edition.clone()),
+        (
+            "minimum supported rust version",
+            or_dash(&package_risk.rust_version),
+        ),
+        (
+            "imported as",
+            join(package_risk.dependency_kinds.iter().collect()),
+        ),
+        (
+            "imported by",
+            join(package_risk.root_importers.iter().collect()),
+        ),
+        ("procedural macro", package_risk.is_proc_macro.to_string()),
+        ("lines of code", package_risk.loc.to_string()),
+        ("lines of rust code", package_risk.rust_loc.to_string()),
+        ("lines of unsafe code", package_risk.unsafe_loc.to_string()),
+        (
+            "lines of unsafe code (with dependencies)",
+            package_risk.total_unsafe_loc.to_string(),
+        ),
+        (
+            "forbids unsafe code",
+            package_risk.forbids_unsafe.to_string(),
+        ),
+        (
+            "direct dependencies",
+            package_risk.direct_dependencies.len().to_string(),
+        ),
+        (
+            "transitive dependencies",
+            package_risk.transitive_dependencies.len().to_string(),
+        ),
+        (
+            "dependencies only introduced by it",
+            package_risk.exclusive_deps_introduced.len().to_string(),
+        ),
+        ("stars", or_dash(&package_risk.stargazers_count)),
+        ("last commit", or_dash(&package_risk.last_commit)),
+        (
+            "active contributors",
+            or_dash(&package_risk.active_contributors),
+        ),
+        ("archived", package_risk.upstream_archived.to_string()),
+        ("latest version", or_dash(&package_risk.latest_version)),
+        ("latest release", or_dash(&package_risk.latest_release)),
+        ("downloads", or_dash(&package_risk.downloads_total)),
+        ("owners", join(package_risk.owners.iter().collect())),
+        ("yanked", package_risk.yanked.to_string()),
+        ("vetted", package_risk.vetted.to_string()),
+        (
+            "checksum verified",
+            or_dash(&package_risk.checksum_verified),
+        ),
+        ("network access", package_risk.network_access.to_string()),
+        (
+            "capabilities",
+            join(package_risk.capabilities.iter().collect()),
+        ),
+    ];
+    metrics
+        .into_iter()
+        .map(|(label, value)| SiteMetric { label, value })
+        .collect()
+}
+
+/// parses a `file:line` location (possibly followed by a description)
+fn parse_location(location: &str) -> Option<(&str, usize)> {
+    let location = location.split(' ').next()?;
+    let mut line_file = location.rsplitn(2, ':');
+    let line = line_file.next()?.parse().ok()?;
+    let file = line_file.next()?;
+    Some((file, line))
+}
+
+/// the files of a package with flagged code, and the flag of each flagged line:
+/// unsafe code, FFI (extern blocks and exported symbols) and network access.
+/// Files that look obfuscated are flagged as a whole (with the reason).
+fn site_flagged_files(
+    package_risk: &PackageRisk,
+) -> BTreeMap<&str, (Option<&str>, BTreeMap<usize, &'static str>)> {
+    let mut files: BTreeMap<&str, (Option<&str>, BTreeMap<usize, &'static str>)> = BTreeMap::new();
+    for location in &package_risk.unsafe_locations {
+        let flag = if location.kind == "extern" {
+            "ffi"
+        } else {
+            "unsafe"
+        };
+        let (_, lines) = files.entry(location.file.as_str()).or_default();
+        for line in location.line..location.line + location.span.max(1) {
+            lines.insert(line, flag);
+        }
+    }
+    let locations = package_risk
+        .symbol_exports
+        .iter()
+        .map(|location| (location, "ffi"))
+        .chain(
+            package_risk
+                .network_evidence
+                .iter()
+                .map(|location| (location, "network")),
+        );
+    for (location, flag) in locations {
+        if let Some((file, line)) = parse_location(location) {
+            let (_, lines) = files.entry(file).or_default();
+            lines.entry(line).or_insert(flag);
+        }
+    }
+    for suspicious_file in &package_risk.suspicious_files {
+        // `file (reason)`
+        let mut file_reason = suspicious_file.splitn(2, " (");
+        let file = file_reason.next().unwrap_or_default();
+        let reason = file_reason
+            .next()
+            .map(|reason| reason.trim_end_matches(')'));
+        let (suspicious, _) = files.entry(file).or_default();
+        *suspicious = reason;
+    }
+    files
+}
+
+/// writes a page per flagged source file of a package (in `packages/<package>/`),
+/// returns the files written
+fn write_site_sources(
+    packages_dir: &Path,
+    name: &str,
+    package_id: &str,
+    package_risk: &PackageRisk,
+    assets: Option<&HtmlAssets>,
+) -> io::Result<Vec<String>> {
+    let package_dir = match package_risk.manifest_path.parent() {
+        Some(package_dir) => package_dir,
+        None => return Ok(Vec::new()),
+    };
+    let source_dir = packages_dir.join(site_source_dir(package_id));
+    let mut written = Vec::new();
+    for (file, (suspicious, flagged_lines)) in site_flagged_files(package_risk) {
+        // only write files inside the package
+        let file_path = Path::new(file);
+        let inside_package = file_path
+            .components()
+            .all(|component| matches!(component, std::path::Component::Normal(_)));
+        if !inside_package {
+            continue;
+        }
+        // binary files can't be displayed
+        let contents = match std::fs::read_to_string(package_dir.join(file_path)) {
+            Ok(contents) => contents,
+            Err(_) => continue,
+        };
+        let lines = contents
+            .lines()
+            .enumerate()
+            .map(|(idx, text)| SiteSourceLine {
+                number: idx + 1,
+                text,
+                flag: flagged_lines.get(&(idx + 1)).cloned().unwrap_or(""),
+            })
+            .collect();
+        // the page is in packages/<package>/<file>.html
+        let root = "../".repeat(1 + file_path.components().count());
+        let page = SiteSource {
+            title: format!("{} in {}", file, package_id),
+            assets,
+            package_page: format!("{}packages/{}", root, site_page(package_id)),
+            root,
+            name,
+            package_id,
+            file,
+            suspicious,
+            lines,
+        };
+        let page_path = source_dir.join(format!("{}.html", file));
+        if let Some(parent) = page_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(page_path, page.render().unwrap())?;
+        written.push(file.to_owned());
+    }
+    Ok(written)
+}
+
+/// writes the result as a static site in `site_dir`,
+/// with the flagged source files of the dependencies if `with_sources` is set
+pub fn write_site(site_dir: &Path, context: &Context, with_sources: bool) -> io::Result<()> {
+    let JsonResult {
+        root_crates,
+        main_dependencies,
+        analysis_result,
+        ..
+    } = &context.report.result;
+    let findings = findings::get_findings(main_dependencies, analysis_result);
+    let packages_dir = site_dir.join("packages");
+    std::fs::create_dir_all(&packages_dir)?;
+
+    // index
+    let root_crates: Vec<&str> = root_crates.iter().map(|root| root.as_str()).collect();
+    let index = SiteIndex {
+        title: context.name,
+        assets: context.html_assets,
+        root: "",
+        name: context.name,
+        root_crates: root_crates.join(", "),
+        metadata: &context.report.metadata,
+        totals: context.report.result.totals(),
+        rows: site_rows(main_dependencies, analysis_result),
+    };
+    std::fs::write(site_dir.join("index.html"), index.render().unwrap())?;
+
+    // a page per package
+    for (package_id, package_risk) in analysis_result {
+        let sources = if with_sources && !package_risk.internal {
+            write_site_sources(
+                &packages_dir,
+                context.name,
+                package_id,
+                package_risk,
+                context.html_assets,
+            )?
+        } else {
+            Vec::new()
+        };
+        let page = SitePackage {
+            title: package_id,
+            assets: context.html_assets,
+            root: "../",
+            name: context.name,
+            package_id,
+            package_risk,
+            findings: findings
+                .iter()
+                .filter(|finding| &finding.package_id == package_id)
+                .map(|finding| finding.message.clone())
+                .collect(),
+            metrics: site_metrics(package_risk),
+            tree: site_tree(analysis_result, package_id),
+            source_dir: site_source_dir(package_id),
+            sources,
+        };
+        std::fs::write(
+            packages_dir.join(site_page(package_id)),
+            page.render().unwrap(),
+        )?;
+    }
+
+    Ok(())
+}
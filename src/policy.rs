@@ -0,0 +1,176 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::analysis::PackageRisk;
+
+//
+// Policy
+// ======
+// thresholds the dependencies must respect, checked after the analysis,
+// so that a CI job can fail when a risky dependency is pulled
+//
+
+/// the rules of the policy (`None` or `false` for the rules that are not enforced)
+#[derive(Default)]
+pub struct Policy {
+    /// maximum lines of unsafe code in a dependency
+    pub max_unsafe_loc: Option<u64>,
+    /// maximum number of third-party dependencies added since the baseline
+    pub max_new_dependencies: Option<u64>,
+    /// minimum number of stars of the repository of a dependency
+    pub min_stars: Option<u64>,
+    /// no dependency affected by a known advisory
+    pub no_advisories: bool,
+    /// no yanked version pulled
+    pub no_yanked: bool,
+    /// only the violations that are new or worse than in the baseline count
+    pub only_regressions: bool,
+}
+
+/// Violation is a rule of the policy broken by a dependency (or by the whole tree)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Violation {
+    /// the rule broken (named after its command-line argument)
+    pub rule: String,
+    /// the dependency (`name@version`, as in the analysis result)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package_id: Option<String>,
+    pub message: String,
+    /// the violation was already in the baseline (and didn't get worse),
+    /// so it doesn't count with `--only-regressions`
+    #[serde(default)]
+    pub in_baseline: bool,
+}
+
+/// names of the third-party dependencies of an analysis
+fn third_party(analysis_result: &BTreeMap<String, PackageRisk>) -> BTreeSet<&str> {
+    analysis_result
+        .values()
+        .filter(|package_risk| !package_risk.internal)
+        .map(|package_risk| package_risk.name.as_str())
+        .collect()
+}
+
+impl Policy {
+    /// lists the violations of the policy, by package
+    /// (new dependencies are only checked if there's a `baseline` to compare to,
+    /// and violations are only compared to the baseline with `only_regressions`)
+    pub fn check(
+        &self,
+        analysis_result: &BTreeMap<String, PackageRisk>,
+        baseline: Option<&BTreeMap<String, PackageRisk>>,
+    ) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        for (package_id, package_risk) in analysis_result {
+            if package_risk.internal {
+                continue;
+            }
+            let name = &package_risk.name;
+
+            // the versions of the same crate in the baseline
+            let previous: Vec<&PackageRisk> = match baseline {
+                Some(baseline) if self.only_regressions => baseline
+                    .values()
+                    .filter(|previous| &previous.name == name)
+                    .collect(),
+                _ => Vec::new(),
+            };
+            let previously =
+                |broken: &dyn Fn(&PackageRisk) -> bool| previous.iter().any(|p| broken(p));
+
+            let mut violation = |rule: &str, message: String, in_baseline: bool| {
+                violations.push(Violation {
+                    rule: rule.to_owned(),
+                    package_id: Some(package_id.clone()),
+                    message,
+                    in_baseline,
+                })
+            };
+
+            if let Some(max_unsafe_loc) = self.max_unsafe_loc {
+                if package_risk.unsafe_loc > max_unsafe_loc {
+                    let message = format!(
+                        "{} has {} lines of unsafe code (at most {} allowed)",
+                        name, package_risk.unsafe_loc, max_unsafe_loc
+                    );
+                    // (it's worse if there's more unsafe code than before)
+                    let in_baseline =
+                        previously(&|previous| previous.unsafe_loc >= package_risk.unsafe_loc);
+                    violation("max-unsafe-loc", message, in_baseline);
+                }
+            }
+
+            // dependencies without a known repository are not checked
+            if let (Some(min_stars), Some(stars)) = (self.min_stars, package_risk.stargazers_count)
+            {
+                if stars < min_stars {
+                    let message = format!(
+                        "{} has {} stars (at least {} required)",
+                        name, stars, min_stars
+                    );
+                    let in_baseline = previously(&|previous| {
+                        previous
+                            .stargazers_count
+                            .map_or(false, |stars| stars < min_stars)
+                    });
+                    violation("min-stars", message, in_baseline);
+                }
+            }
+
+            if self.no_advisories && !package_risk.known_advisories.is_empty() {
+                let ids: Vec<&str> = package_risk
+                    .known_advisories
+                    .iter()
+                    .map(|advisory| advisory.id.as_str())
+                    .collect();
+                let message = format!("{} is affected by {}", name, ids.join(", "));
+                // (it's worse if there's a new advisory)
+                let in_baseline = package_risk.known_advisories.iter().all(|advisory| {
+                    previously(&|previous| {
+                        previous
+                            .known_advisories
+                            .iter()
+                            .any(|known| known.id == advisory.id)
+                    })
+                });
+                violation("no-advisories", message, in_baseline);
+            }
+
+            if self.no_yanked && package_risk.yanked {
+                let mut versions: Vec<&String> = package_risk.versions.iter().collect();
+                versions.sort();
+                let versions: Vec<&str> = versions.iter().map(|version| version.as_str()).collect();
+                let message = format!("{} {} was yanked from crates.io", name, versions.join(", "));
+                // (yanking is about a version, so it's only the same violation for the same version)
+                let in_baseline = self.only_regressions
+                    && baseline
+                        .and_then(|baseline| baseline.get(package_id))
+                        .map_or(false, |previous| previous.yanked);
+                violation("no-yanked", message, in_baseline);
+            }
+        }
+
+        if let (Some(max_new_dependencies), Some(baseline)) = (self.max_new_dependencies, baseline)
+        {
+            let previous = third_party(baseline);
+            let added: Vec<&str> = third_party(analysis_result)
+                .difference(&previous)
+                .cloned()
+                .collect();
+            if added.len() as u64 > max_new_dependencies {
+                violations.push(Violation {
+                    rule: "max-new-dependencies".to_owned(),
+                    package_id: None,
+                    message: format!(
+                        "{} new dependencies since the baseline (at most {} allowed): {}",
+                        added.len(),
+                        max_new_dependencies,
+                        added.join(", ")
+                    ),
+                    in_baseline: false,
+                });
+            }
+        }
+        violations
+    }
+}
@@ -0,0 +1,1812 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::PackageRisk;
+use crate::findings::{self, Finding, Level};
+use crate::policy::Violation;
+
+//
+// Reports
+// =======
+// machine-readable output formats, built from the findings of an analysis
+//
+
+/// finds the path of the Cargo.lock of the workspace a manifest belongs to
+fn find_lockfile_path(manifest_path: &Path) -> Option<PathBuf> {
+    manifest_path
+        .ancestors()
+        .skip(1)
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|lockfile| lockfile.is_file())
+}
+
+/// splits the `name@version` of a dependency in the analysis result
+fn split_package_id(package_id: &str) -> (&str, &str) {
+    let mut name_version = package_id.splitn(2, '@');
+    let name = name_version.next().unwrap_or_default();
+    (name, name_version.next().unwrap_or_default())
+}
+
+/// finds the Cargo.lock of the workspace a manifest belongs to
+pub fn find_lockfile(manifest_path: &Path) -> Option<String> {
+    find_lockfile_path(manifest_path).and_then(|lockfile| fs::read_to_string(lockfile).ok())
+}
+
+/// returns the line (starting at 1) where each `(name, version)` is listed in a Cargo.lock
+fn lockfile_lines(lockfile: &str) -> HashMap<(String, String), usize> {
+    let mut lines = HashMap::new();
+    let mut current: Option<(String, usize)> = None;
+    for (idx, line) in lockfile.lines().enumerate() {
+        // parses `key = "value"`
+        let value = |line: &str, key: &str| {
+            let mut key_value = line.splitn(2, '=');
+            if key_value.next()?.trim() != key {
+                return None;
+            }
+            Some(key_value.next()?.trim().trim_matches('"').to_owned())
+        };
+        if let Some(name) = value(line, "name") {
+            current = Some((name, idx + 1));
+        } else if let Some(version) = value(line, "version") {
+            if let Some((name, line_number)) = current.take() {
+                lines.insert((name, version), line_number);
+            }
+        }
+    }
+    lines
+}
+
+//
+// SARIF
+// -----
+// https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html
+//
+
+/// produces a SARIF 2.1.0 log of the findings,
+/// each finding is located at the dependency's entry in Cargo.lock
+pub fn to_sarif(manifest_path: &Path, findings: &[Finding]) -> serde_json::Value {
+    let lockfile_lines = find_lockfile(manifest_path)
+        .map(|lockfile| lockfile_lines(&lockfile))
+        .unwrap_or_default();
+
+    let rules: Vec<_> = findings::RULES
+        .iter()
+        .map(|rule| {
+            serde_json::json!({
+                "id": rule.id,
+                "shortDescription": { "text": rule.description },
+                "defaultConfiguration": { "level": rule.level.as_str() },
+            })
+        })
+        .collect();
+
+    let results: Vec<_> = findings
+        .iter()
+        .map(|finding| {
+            let mut physical_location = serde_json::json!({
+                "artifactLocation": { "uri": "Cargo.lock", "uriBaseId": "%SRCROOT%" },
+            });
+            let line = finding.versions.iter().find_map(|version| {
+                lockfile_lines.get(&(finding.package.clone(), version.clone()))
+            });
+            if let Some(line) = line {
+                physical_location["region"] = serde_json::json!({ "startLine": line });
+            }
+            serde_json::json!({
+                "ruleId": finding.rule.id,
+                "level": finding.level.as_str(),
+                "message": { "text": finding.message },
+                "locations": [{ "physicalLocation": physical_location }],
+                "partialFingerprints": {
+                    "dephell/v1": format!("{}:{}", finding.rule.id, finding.message),
+                },
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "cargo-dephell",
+                    "informationUri": "https://github.com/mimoo/cargo-dephell",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
+//
+// CycloneDX
+// ---------
+// https://cyclonedx.org/docs/1.5/json/
+//
+
+/// package URL of a crate (https://github.com/package-url/purl-spec)
+fn purl(name: &str, version: &str) -> String {
+    format!("pkg:cargo/{}@{}", name, version)
+}
+
+/// every scalar metric of a dependency, as CycloneDX properties (`dephell:<metric>`)
+fn risk_properties(package_risk: &PackageRisk) -> Vec<serde_json::Value> {
+    let metrics = match serde_json::to_value(package_risk) {
+        Ok(serde_json::Value::Object(metrics)) => metrics,
+        _ => return Vec::new(),
+    };
+    let mut properties = Vec::new();
+    for (metric, value) in metrics {
+        let value = match value {
+            serde_json::Value::Bool(value) => value.to_string(),
+            serde_json::Value::Number(value) => value.to_string(),
+            serde_json::Value::String(value) => value,
+            // null, lists and objects have no natural string representation
+            _ => continue,
+        };
+        properties.push(serde_json::json!({
+            "name": format!("dephell:{}", metric),
+            "value": value,
+        }));
+    }
+    properties.push(serde_json::json!({
+        "name": "dephell:known_advisories",
+        "value": package_risk.known_advisories.len().to_string(),
+    }));
+    properties
+}
+
+/// produces a CycloneDX 1.5 SBOM of the third-party dependencies,
+/// with the metrics of dephell as properties of each component
+pub fn to_cyclonedx(
+    name: &str,
+    main_dependencies: &BTreeSet<String>,
+    analysis_result: &BTreeMap<String, PackageRisk>,
+    timestamp: chrono::DateTime<chrono::Utc>,
+) -> serde_json::Value {
+    // sorted, so that the output is stable
+    let mut packages: Vec<(&String, &PackageRisk)> = analysis_result.iter().collect();
+    packages.sort_by(|a, b| a.0.cmp(b.0));
+    let versions = |package_risk: &PackageRisk| {
+        let mut versions: Vec<String> = package_risk.versions.iter().cloned().collect();
+        versions.sort();
+        versions
+    };
+    // (workspace crates are not components)
+    let refs = |package_id: &str| {
+        analysis_result
+            .get(package_id)
+            .filter(|package_risk| !package_risk.internal)
+            .map(|package_risk| {
+                versions(package_risk)
+                    .iter()
+                    .map(|version| purl(&package_risk.name, version))
+                    .collect::<Vec<String>>()
+            })
+            .unwrap_or_else(Vec::new)
+    };
+
+    let mut components = Vec::new();
+    let mut dependencies = Vec::new();
+    let mut vulnerabilities = Vec::new();
+    for (package_id, package_risk) in &packages {
+        if package_risk.internal {
+            continue;
+        }
+        let name = &package_risk.name;
+        let depends_on: Vec<String> = package_risk
+            .direct_dependencies
+            .iter()
+            .flat_map(|dependency| refs(dependency))
+            .collect();
+        for version in versions(package_risk) {
+            let bom_ref = purl(name, &version);
+            let scope = if package_risk.used {
+                "required"
+            } else {
+                "optional"
+            };
+            let mut component = serde_json::json!({
+                "type": "library",
+                "bom-ref": bom_ref,
+                "name": name,
+                "version": version,
+                "purl": bom_ref,
+                "scope": scope,
+                "properties": risk_properties(package_risk),
+            });
+            if let Some(description) = &package_risk.description {
+                component["description"] = serde_json::json!(description);
+            }
+            if let Some(license) = &package_risk.license {
+                component["licenses"] = serde_json::json!([{ "expression": license }]);
+            }
+            if let Some(repo) = &package_risk.repo {
+                component["externalReferences"] =
+                    serde_json::json!([{ "type": "vcs", "url": repo }]);
+            }
+            components.push(component);
+            dependencies.push(serde_json::json!({
+                "ref": bom_ref,
+                "dependsOn": depends_on,
+            }));
+        }
+        for advisory in &package_risk.known_advisories {
+            let mut vulnerability = serde_json::json!({
+                "id": advisory.id,
+                "affects": refs(package_id)
+                    .iter()
+                    .map(|bom_ref| serde_json::json!({ "ref": bom_ref }))
+                    .collect::<Vec<_>>(),
+            });
+            if let Some(severity) = &advisory.severity {
+                // (the severities of CycloneDX)
+                let severity = match severity.as_str() {
+                    "critical" | "high" | "medium" | "low" | "info" | "none" => severity.as_str(),
+                    "moderate" => "medium",
+                    _ => "unknown",
+                };
+                vulnerability["ratings"] = serde_json::json!([{ "severity": severity }]);
+            }
+            vulnerabilities.push(vulnerability);
+        }
+    }
+
+    // the project depends on the third-party dependencies of the root and workspace crates
+    let workspace_dependencies = packages
+        .iter()
+        .filter(|(_, package_risk)| package_risk.internal)
+        .flat_map(|(_, package_risk)| package_risk.direct_dependencies.iter());
+    let main_dependencies: BTreeSet<&String> = main_dependencies
+        .iter()
+        .chain(workspace_dependencies)
+        .collect();
+    let root_depends_on: Vec<String> = main_dependencies
+        .into_iter()
+        .flat_map(|dependency| refs(dependency))
+        .collect();
+    dependencies.push(serde_json::json!({
+        "ref": name,
+        "dependsOn": root_depends_on,
+    }));
+
+    serde_json::json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "version": 1,
+        "metadata": {
+            "timestamp": timestamp.to_rfc3339(),
+            "tools": {
+                "components": [{
+                    "type": "application",
+                    "name": "cargo-dephell",
+                    "version": env!("CARGO_PKG_VERSION"),
+                }],
+            },
+            "component": {
+                "type": "application",
+                "bom-ref": name,
+                "name": name,
+            },
+        },
+        "components": components,
+        "dependencies": dependencies,
+        "vulnerabilities": vulnerabilities,
+    })
+}
+
+//
+// SPDX
+// ----
+// https://spdx.github.io/spdx-spec/v2.3/
+//
+
+/// SPDX identifiers can only contain letters, numbers, `.` and `-`
+fn spdx_id(name: &str, version: &str) -> String {
+    let id: String = format!("{}-{}", name, version)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!("SPDXRef-Package-{}", id)
+}
+
+/// converts the deprecated `MIT/Apache-2.0` syntax of Cargo.toml to an SPDX expression
+fn spdx_license(license: &Option<String>) -> String {
+    match license {
+        Some(license) => license
+            .split('/')
+            .map(|license| license.trim())
+            .collect::<Vec<_>>()
+            .join(" OR "),
+        None => "NOASSERTION".to_string(),
+    }
+}
+
+/// produces an SPDX 2.3 document (as JSON) of the dependencies,
+/// with their versions, checksums and license expressions
+pub fn to_spdx(
+    name: &str,
+    main_dependencies: &BTreeSet<String>,
+    analysis_result: &BTreeMap<String, PackageRisk>,
+    timestamp: chrono::DateTime<chrono::Utc>,
+) -> serde_json::Value {
+    let root_id = "SPDXRef-Package-root".to_string();
+    let mut packages = vec![serde_json::json!({
+        "SPDXID": root_id,
+        "name": name,
+        "downloadLocation": "NOASSERTION",
+        "filesAnalyzed": false,
+        "licenseConcluded": "NOASSERTION",
+        "licenseDeclared": "NOASSERTION",
+        "copyrightText": "NOASSERTION",
+    })];
+    let mut relationships = vec![serde_json::json!({
+        "spdxElementId": "SPDXRef-DOCUMENT",
+        "relationshipType": "DESCRIBES",
+        "relatedSpdxElement": root_id,
+    })];
+
+    // sorted, so that the output is stable
+    let mut sorted: Vec<(&String, &PackageRisk)> = analysis_result.iter().collect();
+    sorted.sort_by(|a, b| a.0.cmp(b.0));
+    let ids = |package_id: &str| -> Vec<String> {
+        let package_risk = match analysis_result.get(package_id) {
+            Some(package_risk) => package_risk,
+            None => return Vec::new(),
+        };
+        let mut versions: Vec<&String> = package_risk.versions.iter().collect();
+        versions.sort();
+        versions
+            .into_iter()
+            .map(|version| spdx_id(&package_risk.name, version))
+            .collect()
+    };
+
+    for (_, package_risk) in sorted {
+        let name = &package_risk.name;
+        let mut versions: Vec<&String> = package_risk.versions.iter().collect();
+        versions.sort();
+        for version in versions {
+            let id = spdx_id(name, version);
+            let download_location = if package_risk.internal {
+                "NOASSERTION".to_string()
+            } else {
+                format!(
+                    "https://crates.io/api/v1/crates/{}/{}/download",
+                    name, version
+                )
+            };
+            let mut package = serde_json::json!({
+                "SPDXID": id,
+                "name": name,
+                "versionInfo": version,
+                "downloadLocation": download_location,
+                "filesAnalyzed": false,
+                "licenseConcluded": "NOASSERTION",
+                "licenseDeclared": spdx_license(&package_risk.license),
+                "copyrightText": "NOASSERTION",
+                "externalRefs": [{
+                    "referenceCategory": "PACKAGE-MANAGER",
+                    "referenceType": "purl",
+                    "referenceLocator": purl(name, version),
+                }],
+            });
+            if let Some(checksum) = package_risk.checksums.get(version) {
+                package["checksums"] = serde_json::json!([{
+                    "algorithm": "SHA256",
+                    "checksumValue": checksum,
+                }]);
+            }
+            if let Some(repo) = &package_risk.repo {
+                package["homepage"] = serde_json::json!(repo);
+            }
+            packages.push(package);
+
+            for dependency in &package_risk.direct_dependencies {
+                for dependency_id in ids(dependency) {
+                    relationships.push(serde_json::json!({
+                        "spdxElementId": id,
+                        "relationshipType": "DEPENDS_ON",
+                        "relatedSpdxElement": dependency_id,
+                    }));
+                }
+            }
+        }
+    }
+
+    // the project depends on the direct dependencies of the root crates
+    let mut main_dependencies: Vec<&String> = main_dependencies.iter().collect();
+    main_dependencies.sort();
+    for dependency in main_dependencies {
+        for dependency_id in ids(dependency) {
+            relationships.push(serde_json::json!({
+                "spdxElementId": root_id,
+                "relationshipType": "DEPENDS_ON",
+                "relatedSpdxElement": dependency_id,
+            }));
+        }
+    }
+
+    let created = timestamp.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    serde_json::json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": name,
+        "documentNamespace": format!("https://spdx.org/spdxdocs/cargo-dephell/{}-{}", name, created),
+        "creationInfo": {
+            "created": created,
+            "creators": [format!("Tool: cargo-dephell-{}", env!("CARGO_PKG_VERSION"))],
+        },
+        "packages": packages,
+        "relationships": relationships,
+    })
+}
+
+/// renders an SPDX document produced by `to_spdx` in the tag-value format
+pub fn to_spdx_tag_value(spdx: &serde_json::Value) -> String {
+    let text = |value: &serde_json::Value| match value {
+        serde_json::Value::String(value) => value.clone(),
+        value => value.to_string(),
+    };
+    let mut lines = vec![
+        format!("SPDXVersion: {}", text(&spdx["spdxVersion"])),
+        format!("DataLicense: {}", text(&spdx["dataLicense"])),
+        format!("SPDXID: {}", text(&spdx["SPDXID"])),
+        format!("DocumentName: {}", text(&spdx["name"])),
+        format!("DocumentNamespace: {}", text(&spdx["documentNamespace"])),
+        format!("Created: {}", text(&spdx["creationInfo"]["created"])),
+    ];
+    for creator in spdx["creationInfo"]["creators"]
+        .as_array()
+        .into_iter()
+        .flatten()
+    {
+        lines.push(format!("Creator: {}", text(creator)));
+    }
+
+    for package in spdx["packages"].as_array().into_iter().flatten() {
+        lines.push(String::new());
+        lines.push(format!("PackageName: {}", text(&package["name"])));
+        lines.push(format!("SPDXID: {}", text(&package["SPDXID"])));
+        if !package["versionInfo"].is_null() {
+            lines.push(format!("PackageVersion: {}", text(&package["versionInfo"])));
+        }
+        lines.push(format!(
+            "PackageDownloadLocation: {}",
+            text(&package["downloadLocation"])
+        ));
+        lines.push(format!(
+            "FilesAnalyzed: {}",
+            text(&package["filesAnalyzed"])
+        ));
+        if !package["homepage"].is_null() {
+            lines.push(format!("PackageHomePage: {}", text(&package["homepage"])));
+        }
+        for checksum in package["checksums"].as_array().into_iter().flatten() {
+            lines.push(format!(
+                "PackageChecksum: {}: {}",
+                text(&checksum["algorithm"]),
+                text(&checksum["checksumValue"])
+            ));
+        }
+        lines.push(format!(
+            "PackageLicenseConcluded: {}",
+            text(&package["licenseConcluded"])
+        ));
+        lines.push(format!(
+            "PackageLicenseDeclared: {}",
+            text(&package["licenseDeclared"])
+        ));
+        lines.push(format!(
+            "PackageCopyrightText: {}",
+            text(&package["copyrightText"])
+        ));
+        for external_ref in package["externalRefs"].as_array().into_iter().flatten() {
+            lines.push(format!(
+                "ExternalRef: {} {} {}",
+                text(&external_ref["referenceCategory"]),
+                text(&external_ref["referenceType"]),
+                text(&external_ref["referenceLocator"])
+            ));
+        }
+    }
+
+    lines.push(String::new());
+    for relationship in spdx["relationships"].as_array().into_iter().flatten() {
+        lines.push(format!(
+            "Relationship: {} {} {}",
+            text(&relationship["spdxElementId"]),
+            text(&relationship["relationshipType"]),
+            text(&relationship["relatedSpdxElement"])
+        ));
+    }
+    lines.join("\n")
+}
+
+//
+// CSV
+// ---
+//
+
+/// quotes a CSV field if needed (RFC 4180)
+fn csv_field(field: &str) -> String {
+    if field.contains(|c: char| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// produces a CSV table with one row per dependency,
+/// and a column for each numeric (or boolean) metric
+pub fn to_csv(analysis_result: &BTreeMap<String, PackageRisk>) -> String {
+    // sorted, so that the output is stable
+    let mut rows: Vec<(
+        &String,
+        &PackageRisk,
+        serde_json::Map<String, serde_json::Value>,
+    )> = analysis_result
+        .iter()
+        .filter_map(
+            |(name, package_risk)| match serde_json::to_value(package_risk) {
+                Ok(serde_json::Value::Object(metrics)) => Some((name, package_risk, metrics)),
+                _ => None,
+            },
+        )
+        .collect();
+    rows.sort_by(|a, b| a.0.cmp(b.0));
+
+    // a metric gets a column if it's numeric for at least one dependency
+    // (optional metrics are null when unknown)
+    let mut columns: Vec<String> = Vec::new();
+    for (_, _, metrics) in &rows {
+        for (metric, value) in metrics {
+            if (value.is_number() || value.is_boolean()) && !columns.contains(metric) {
+                columns.push(metric.clone());
+            }
+        }
+    }
+    columns.sort();
+
+    let mut header = vec!["name".to_string(), "versions".to_string()];
+    header.extend(columns.iter().cloned());
+    header.push("known_advisories".to_string());
+    let mut csv = header.join(",");
+    csv.push('\n');
+
+    for (_, package_risk, metrics) in rows {
+        let mut versions: Vec<&String> = package_risk.versions.iter().collect();
+        versions.sort();
+        let versions: Vec<&str> = versions.iter().map(|version| version.as_str()).collect();
+        let mut row = vec![
+            csv_field(&package_risk.name),
+            csv_field(&versions.join(" ")),
+        ];
+        for column in &columns {
+            let field = match metrics.get(column) {
+                Some(serde_json::Value::Number(value)) => value.to_string(),
+                Some(serde_json::Value::Bool(value)) => value.to_string(),
+                _ => String::new(),
+            };
+            row.push(field);
+        }
+        row.push(package_risk.known_advisories.len().to_string());
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// columns of the legal inventory
+/// (don't change them: unlike the full CSV, this one is meant to be stable)
+const INVENTORY_COLUMNS: &[&str] = &["name", "version", "license", "repository", "authors"];
+
+/// produces a CSV inventory of the third-party dependencies for compliance:
+/// one row per version pulled, with its license, repository and authors (no risk metrics)
+pub fn to_inventory_csv(analysis_result: &BTreeMap<String, PackageRisk>) -> String {
+    let mut csv = INVENTORY_COLUMNS.join(",");
+    csv.push('\n');
+    for package_risk in analysis_result.values() {
+        if package_risk.internal {
+            continue;
+        }
+        for version in &package_risk.versions {
+            let row = [
+                csv_field(&package_risk.name),
+                csv_field(version),
+                csv_field(package_risk.license.as_deref().unwrap_or_default()),
+                csv_field(package_risk.repo.as_deref().unwrap_or_default()),
+                csv_field(&package_risk.authors.join("; ")),
+            ];
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+    }
+    csv
+}
+
+//
+// Markdown
+// --------
+// a compact summary, to be posted as a pull request comment
+//
+
+/// number of dependencies listed in the markdown summary
+const MARKDOWN_TOP_RISKY: usize = 10;
+
+/// shows how a number changed since the baseline, if there's one
+fn delta(current: u64, previous: Option<u64>) -> String {
+    match previous {
+        Some(previous) if current > previous => format!(" (+{})", current - previous),
+        Some(previous) if current < previous => format!(" (-{})", previous - current),
+        _ => String::new(),
+    }
+}
+
+/// Totals are numbers about the whole dependency tree (third-party dependencies only)
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+pub struct Totals {
+    pub dependencies: u64,
+    pub direct_dependencies: u64,
+    pub rust_loc: u64,
+    pub unsafe_loc: u64,
+    pub advisories: u64,
+    pub yanked: u64,
+}
+
+impl Totals {
+    pub fn new(
+        main_dependencies: &BTreeSet<String>,
+        analysis_result: &BTreeMap<String, PackageRisk>,
+    ) -> Self {
+        let mut totals = Totals::default();
+        for (package_id, package_risk) in analysis_result {
+            if package_risk.internal {
+                continue;
+            }
+            totals.dependencies += 1;
+            if main_dependencies.contains(package_id) {
+                totals.direct_dependencies += 1;
+            }
+            totals.rust_loc += package_risk.rust_loc;
+            totals.unsafe_loc += package_risk.unsafe_loc;
+            totals.advisories += package_risk.known_advisories.len() as u64;
+            if package_risk.yanked {
+                totals.yanked += 1;
+            }
+        }
+        totals
+    }
+}
+
+/// ranks the third-party dependencies, riskiest first:
+/// the ones with the most serious findings, then the most unsafe code
+fn rank_dependencies<'a>(
+    analysis_result: &'a BTreeMap<String, PackageRisk>,
+    findings: &'a [Finding],
+) -> Vec<(&'a String, &'a PackageRisk, Vec<&'a Finding>)> {
+    let mut ranked: Vec<(&String, &PackageRisk, Vec<&Finding>)> = analysis_result
+        .iter()
+        .filter(|(_, package_risk)| !package_risk.internal)
+        .map(|(package_id, package_risk)| {
+            let findings = findings
+                .iter()
+                .filter(|finding| &finding.package_id == package_id)
+                .collect();
+            (package_id, package_risk, findings)
+        })
+        .collect();
+    let severity = |findings: &[&Finding]| {
+        let count = |level| {
+            findings
+                .iter()
+                .filter(|finding| finding.level == level)
+                .count()
+        };
+        (
+            count(Level::Error),
+            count(Level::Warning),
+            count(Level::Note),
+        )
+    };
+    ranked.sort_by(|a, b| {
+        (severity(&b.2), b.1.unsafe_loc, a.0).cmp(&(severity(&a.2), a.1.unsafe_loc, b.0))
+    });
+    ranked
+}
+
+/// keeps only the `top` riskiest third-party dependencies (and the workspace crates),
+/// the lists of dependencies of the packages kept only reference packages kept
+pub fn keep_top(
+    top: usize,
+    main_dependencies: &mut BTreeSet<String>,
+    analysis_result: &mut BTreeMap<String, PackageRisk>,
+) {
+    let findings = findings::get_findings(main_dependencies, analysis_result);
+    let riskiest = rank_dependencies(analysis_result, &findings)
+        .into_iter()
+        .take(top)
+        .map(|(package_id, _, _)| package_id.clone());
+    let kept: BTreeSet<String> = analysis_result
+        .iter()
+        .filter(|(_, package_risk)| package_risk.internal)
+        .map(|(package_id, _)| package_id.clone())
+        .chain(riskiest)
+        .collect();
+
+    let keep = |dependencies: &mut BTreeSet<String>| {
+        *dependencies = std::mem::take(dependencies)
+            .into_iter()
+            .filter(|dependency| kept.contains(dependency))
+            .collect();
+    };
+    keep(main_dependencies);
+    *analysis_result = std::mem::take(analysis_result)
+        .into_iter()
+        .filter(|(package_id, _)| kept.contains(package_id))
+        .collect();
+    for package_risk in analysis_result.values_mut() {
+        keep(&mut package_risk.direct_dependencies);
+        keep(&mut package_risk.transitive_dependencies);
+        package_risk
+            .exclusive_deps_introduced
+            .retain(|dependency| kept.contains(dependency));
+    }
+}
+
+/// produces a markdown summary of the analysis: totals, and the riskiest dependencies
+/// (the ones with the most serious findings, then the most unsafe code),
+/// with the violations of the policy,
+/// and what changed since the `baseline` (a previous analysis) if given
+pub fn to_markdown(
+    name: &str,
+    totals: &Totals,
+    analysis_result: &BTreeMap<String, PackageRisk>,
+    findings: &[Finding],
+    violations: &[Violation],
+    baseline: Option<(&Totals, &BTreeMap<String, PackageRisk>)>,
+) -> String {
+    let mut markdown = format!("### cargo dephell report for `{}`\n\n", name);
+
+    // totals
+    let previous = |total: fn(&Totals) -> u64| baseline.map(|(totals, _)| total(totals));
+    markdown.push_str("| | total |\n|---|---:|\n");
+    let rows: [(&str, fn(&Totals) -> u64); 6] = [
+        ("dependencies", |totals| totals.dependencies),
+        ("direct dependencies", |totals| totals.direct_dependencies),
+        ("lines of rust code", |totals| totals.rust_loc),
+        ("lines of unsafe code", |totals| totals.unsafe_loc),
+        ("known advisories", |totals| totals.advisories),
+        ("yanked versions", |totals| totals.yanked),
+    ];
+    for (label, total) in rows.iter() {
+        markdown.push_str(&format!(
+            "| {} | {}{} |\n",
+            label,
+            total(totals),
+            delta(total(totals), previous(*total))
+        ));
+    }
+
+    // new and removed dependencies
+    if let Some((_, baseline)) = baseline {
+        let third_party = |analysis_result: &BTreeMap<String, PackageRisk>| -> BTreeSet<String> {
+            analysis_result
+                .iter()
+                .filter(|(_, package_risk)| !package_risk.internal)
+                .map(|(name, _)| name.clone())
+                .collect()
+        };
+        let (current, previous) = (third_party(analysis_result), third_party(baseline));
+        let mut added: Vec<&String> = current.difference(&previous).collect();
+        let mut removed: Vec<&String> = previous.difference(&current).collect();
+        added.sort();
+        removed.sort();
+        let list = |names: Vec<&String>| {
+            let names: Vec<String> = names.iter().map(|name| format!("`{}`", name)).collect();
+            names.join(", ")
+        };
+        if !added.is_empty() {
+            markdown.push_str(&format!("\n**new dependencies:** {}\n", list(added)));
+        }
+        if !removed.is_empty() {
+            markdown.push_str(&format!("\n**removed dependencies:** {}\n", list(removed)));
+        }
+    }
+
+    // violations of the policy
+    if !violations.is_empty() {
+        markdown.push_str("\n#### policy violations\n\n");
+        for violation in violations {
+            if violation.in_baseline {
+                markdown.push_str(&format!(
+                    "* :warning: {} (already in the baseline)\n",
+                    violation.message
+                ));
+            } else {
+                markdown.push_str(&format!("* :x: {}\n", violation.message));
+            }
+        }
+    }
+
+    // riskiest dependencies
+    let risky: Vec<_> = rank_dependencies(analysis_result, findings)
+        .into_iter()
+        .filter(|(_, package_risk, findings)| !findings.is_empty() || package_risk.unsafe_loc > 0)
+        .collect();
+    if risky.is_empty() {
+        markdown.push_str("\nno risky dependency found :tada:\n");
+        return markdown;
+    }
+    markdown.push_str("\n#### riskiest dependencies\n\n");
+    markdown.push_str("| dependency | versions | unsafe LOC | findings |\n|---|---|---:|---|\n");
+    for (package_id, package_risk, findings) in risky.into_iter().take(MARKDOWN_TOP_RISKY) {
+        // the same version, or another version of the same crate
+        // (baselines from before `name@version` keys are keyed by name)
+        let previous = baseline.and_then(|(_, baseline)| {
+            baseline.get(package_id).or_else(|| {
+                baseline
+                    .iter()
+                    .find(|(key, previous)| {
+                        previous.name == package_risk.name || *key == &package_risk.name
+                    })
+                    .map(|(_, previous)| previous)
+            })
+        });
+        let new_marker = match (baseline, previous) {
+            (Some(_), None) => " :new:",
+            _ => "",
+        };
+        let mut versions: Vec<&String> = package_risk.versions.iter().collect();
+        versions.sort();
+        let versions: Vec<&str> = versions.iter().map(|version| version.as_str()).collect();
+        let findings: Vec<String> = findings
+            .iter()
+            .map(|finding| finding.message.clone())
+            .collect();
+        markdown.push_str(&format!(
+            "| `{}`{} | {} | {}{} | {} |\n",
+            package_risk.name,
+            new_marker,
+            versions.join(", "),
+            package_risk.unsafe_loc,
+            delta(
+                package_risk.unsafe_loc,
+                previous.map(|previous| previous.unsafe_loc)
+            ),
+            findings.join("<br>"),
+        ));
+    }
+    markdown
+}
+
+//
+// Terminal
+// --------
+//
+
+/// produces a table of the dependencies, riskiest first,
+/// with colors if it's printed to a terminal
+pub fn to_table(
+    totals: &Totals,
+    analysis_result: &BTreeMap<String, PackageRisk>,
+    findings: &[Finding],
+    color: bool,
+) -> String {
+    use ansi_term::{Colour, Style};
+
+    // cells of the table, with their style
+    let header = [
+        "#",
+        "dependency",
+        "versions",
+        "unsafe LOC",
+        "stars",
+        "advisories",
+    ];
+    let mut rows: Vec<Vec<(String, Style)>> = Vec::new();
+    for (rank, (_, package_risk, _)) in rank_dependencies(analysis_result, findings)
+        .into_iter()
+        .enumerate()
+    {
+        let versions: Vec<&str> = package_risk.versions.iter().map(|v| v.as_str()).collect();
+        let advisories = package_risk.known_advisories.len();
+        let name_style = if advisories > 0 {
+            Colour::Red.bold()
+        } else {
+            Style::new().bold()
+        };
+        let unsafe_style = match package_risk.unsafe_loc {
+            0 => Colour::Green.normal(),
+            loc if loc > findings::HIGH_UNSAFE_LOC => Colour::Yellow.normal(),
+            _ => Style::new(),
+        };
+        let stars = match package_risk.stargazers_count {
+            Some(stars) => (stars.to_string(), Style::new()),
+            None => ("-".to_owned(), Style::new().dimmed()),
+        };
+        let advisories = if advisories > 0 {
+            (advisories.to_string(), Colour::Red.normal())
+        } else {
+            ("-".to_owned(), Style::new().dimmed())
+        };
+        rows.push(vec![
+            ((rank + 1).to_string(), Style::new().dimmed()),
+            (package_risk.name.clone(), name_style),
+            (versions.join(", "), Style::new()),
+            (package_risk.unsafe_loc.to_string(), unsafe_style),
+            stars,
+            advisories,
+        ]);
+    }
+
+    // pad the cells (before painting them, escape codes have no width)
+    let mut widths: Vec<usize> = header.iter().map(|title| title.len()).collect();
+    for row in &rows {
+        for (width, (text, _)) in widths.iter_mut().zip(row) {
+            *width = std::cmp::max(*width, text.chars().count());
+        }
+    }
+    let line = |cells: Vec<(String, Style)>| {
+        let cells: Vec<String> = cells
+            .into_iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(column, ((text, style), width))| {
+                // names and versions are aligned left, numbers right
+                let padded = if column == 1 || column == 2 {
+                    format!("{:<width$}", text, width = *width)
+                } else {
+                    format!("{:>width$}", text, width = *width)
+                };
+                if color {
+                    style.paint(padded).to_string()
+                } else {
+                    padded
+                }
+            })
+            .collect();
+        cells.join("  ").trim_end().to_owned()
+    };
+
+    let mut table = String::new();
+    let header = header
+        .iter()
+        .map(|title| (title.to_string(), Style::new().underline()))
+        .collect();
+    table.push_str(&line(header));
+    table.push('\n');
+    for row in rows {
+        table.push_str(&line(row));
+        table.push('\n');
+    }
+
+    // totals
+    table.push_str(&format!(
+        "\n{} dependencies ({} direct), {} lines of unsafe code, {} known advisories\n",
+        totals.dependencies, totals.direct_dependencies, totals.unsafe_loc, totals.advisories
+    ));
+    table
+}
+
+//
+// JUnit
+// -----
+// understood by most CI dashboards (jenkins, gitlab, etc.)
+//
+
+/// escapes text for XML
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// produces a JUnit XML report with a test suite per rule,
+/// and a test case per dependency (failing if the dependency violates the rule),
+/// plus a test suite with a failing test case per violation of the policy
+/// (skipped if the violation was already in the baseline)
+pub fn to_junit(
+    analysis_result: &BTreeMap<String, PackageRisk>,
+    findings: &[Finding],
+    violations: &[Violation],
+) -> String {
+    let mut packages: Vec<&String> = analysis_result
+        .iter()
+        .filter(|(_, package_risk)| !package_risk.internal)
+        .map(|(package_id, _)| package_id)
+        .collect();
+    packages.sort();
+
+    let mut suites = Vec::new();
+    let mut total_failures = 0;
+    for rule in findings::RULES {
+        let mut cases = Vec::new();
+        let mut failures = 0;
+        for package_id in &packages {
+            let violations: Vec<&Finding> = findings
+                .iter()
+                .filter(|finding| finding.rule.id == rule.id && &&finding.package_id == package_id)
+                .collect();
+            let case_name = format!("{} {}", xml_escape(package_id), rule.id);
+            if violations.is_empty() {
+                cases.push(format!(
+                    "    <testcase classname=\"dephell.{}\" name=\"{}\"/>",
+                    rule.id, case_name
+                ));
+                continue;
+            }
+            failures += 1;
+            let messages: Vec<String> = violations
+                .iter()
+                .map(|finding| format!("{}: {}", finding.level.as_str(), finding.message))
+                .collect();
+            cases.push(format!(
+                "    <testcase classname=\"dephell.{}\" name=\"{}\">\n      <failure message=\"{}\" type=\"{}\">{}</failure>\n    </testcase>",
+                rule.id,
+                case_name,
+                xml_escape(&messages[0]),
+                rule.level.as_str(),
+                xml_escape(&messages.join("\n"))
+            ));
+        }
+        total_failures += failures;
+        suites.push(format!(
+            "  <testsuite name=\"{}: {}\" tests=\"{}\" failures=\"{}\">\n{}\n  </testsuite>",
+            rule.id,
+            xml_escape(rule.description),
+            packages.len(),
+            failures,
+            cases.join("\n")
+        ));
+    }
+
+    // violations of the policy
+    if !violations.is_empty() {
+        let mut cases = Vec::new();
+        let mut failures = 0;
+        for violation in violations {
+            let case_name = match &violation.package_id {
+                Some(package_id) => format!("{} {}", xml_escape(package_id), violation.rule),
+                None => violation.rule.clone(),
+            };
+            let result = if violation.in_baseline {
+                format!(
+                    "<skipped message=\"already in the baseline: {}\"/>",
+                    xml_escape(&violation.message)
+                )
+            } else {
+                failures += 1;
+                format!(
+                    "<failure message=\"{}\" type=\"policy\">{}</failure>",
+                    xml_escape(&violation.message),
+                    xml_escape(&violation.message)
+                )
+            };
+            cases.push(format!(
+                "    <testcase classname=\"dephell.policy.{}\" name=\"{}\">\n      {}\n    </testcase>",
+                violation.rule, case_name, result
+            ));
+        }
+        total_failures += failures;
+        suites.push(format!(
+            "  <testsuite name=\"policy\" tests=\"{}\" failures=\"{}\">\n{}\n  </testsuite>",
+            violations.len(),
+            failures,
+            cases.join("\n")
+        ));
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites name=\"cargo-dephell\" tests=\"{}\" failures=\"{}\">\n{}\n</testsuites>\n",
+        packages.len() * findings::RULES.len() + violations.len(),
+        total_failures,
+        suites.join("\n")
+    )
+}
+
+//
+// GitHub Actions
+// --------------
+// https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions
+//
+
+/// escapes the message of a workflow command
+fn escape_workflow_data(data: &str) -> String {
+    data.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// escapes a property of a workflow command
+fn escape_workflow_property(property: &str) -> String {
+    escape_workflow_data(property)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
+
+/// produces a workflow command (`::error`, `::warning` or `::notice`) per finding,
+/// and per violation of the policy (`::notice` if it was already in the baseline),
+/// which github displays as annotations on the dependency's entry in Cargo.lock
+pub fn to_github_annotations(
+    manifest_path: &Path,
+    findings: &[Finding],
+    violations: &[Violation],
+) -> String {
+    let lockfile_lines = find_lockfile(manifest_path)
+        .map(|lockfile| lockfile_lines(&lockfile))
+        .unwrap_or_default();
+    // annotations are on files relative to the root of the repository
+    let lockfile_path = find_lockfile_path(manifest_path)
+        .and_then(|lockfile| {
+            let workspace = std::env::var_os("GITHUB_WORKSPACE")
+                .map(PathBuf::from)
+                .or_else(|| std::env::current_dir().ok())?;
+            let lockfile = lockfile.canonicalize().ok()?;
+            let relative = lockfile.strip_prefix(workspace.canonicalize().ok()?).ok()?;
+            Some(relative.to_string_lossy().replace('\\', "/"))
+        })
+        .unwrap_or_else(|| "Cargo.lock".to_owned());
+    let mut annotations = String::new();
+    for finding in findings {
+        let command = match finding.level {
+            Level::Error => "error",
+            Level::Warning => "warning",
+            Level::Note => "notice",
+        };
+        let mut properties = vec![format!("file={}", escape_workflow_property(&lockfile_path))];
+        let line = finding
+            .versions
+            .iter()
+            .find_map(|version| lockfile_lines.get(&(finding.package.clone(), version.clone())));
+        if let Some(line) = line {
+            properties.push(format!("line={}", line));
+        }
+        properties.push(format!(
+            "title={}",
+            escape_workflow_property(&format!(
+                "{}: {}",
+                finding.rule.id, finding.rule.description
+            ))
+        ));
+        annotations.push_str(&format!(
+            "::{} {}::{}\n",
+            command,
+            properties.join(","),
+            escape_workflow_data(&finding.message)
+        ));
+    }
+    for violation in violations {
+        let command = if violation.in_baseline {
+            "notice"
+        } else {
+            "error"
+        };
+        let mut properties = vec![format!("file={}", escape_workflow_property(&lockfile_path))];
+        let line = violation.package_id.as_ref().and_then(|package_id| {
+            let (name, version) = split_package_id(package_id);
+            lockfile_lines.get(&(name.to_owned(), version.to_owned()))
+        });
+        if let Some(line) = line {
+            properties.push(format!("line={}", line));
+        }
+        properties.push(format!(
+            "title={}",
+            escape_workflow_property(&format!("policy: {}", violation.rule))
+        ));
+        annotations.push_str(&format!(
+            "::{} {}::{}\n",
+            command,
+            properties.join(","),
+            escape_workflow_data(&violation.message)
+        ));
+    }
+    annotations
+}
+
+//
+// GitLab
+// ------
+// https://gitlab.com/gitlab-org/security-products/security-report-schemas
+//
+
+/// version of the dependency scanning report schema
+const GITLAB_SCHEMA_VERSION: &str = "15.0.7";
+
+/// severity of a finding, as understood by gitlab
+fn gitlab_severity(finding: &Finding) -> &'static str {
+    let advisory_severity = finding
+        .advisory
+        .as_ref()
+        .and_then(|advisory| advisory.severity.as_deref());
+    match (advisory_severity, finding.level) {
+        (Some("critical"), _) => "Critical",
+        (Some("high"), _) => "High",
+        (Some("medium"), _) => "Medium",
+        (Some("low"), _) => "Low",
+        (Some("none"), _) => "Info",
+        (None, _) if finding.advisory.is_some() => "Unknown",
+        (_, Level::Error) => "High",
+        (_, Level::Warning) => "Medium",
+        (_, Level::Note) => "Info",
+    }
+}
+
+/// produces a gitlab dependency scanning report,
+/// advisories, other findings and violations of the policy are reported as vulnerabilities of Cargo.lock
+pub fn to_gitlab(
+    analysis_result: &BTreeMap<String, PackageRisk>,
+    findings: &[Finding],
+    violations: &[Violation],
+    start_time: chrono::DateTime<chrono::Utc>,
+    end_time: chrono::DateTime<chrono::Utc>,
+) -> serde_json::Value {
+    use sha2::{Digest, Sha256};
+
+    let mut vulnerabilities: Vec<_> = findings
+        .iter()
+        .map(|finding| {
+            // ids must be unique and stable across runs
+            let id = Sha256::digest(
+                format!("{}:{}:{}", finding.rule.id, finding.package, finding.message).as_bytes(),
+            );
+            let version = finding.versions.first().cloned().unwrap_or_default();
+            let mut identifiers = vec![serde_json::json!({
+                "type": "dephell_rule",
+                "name": finding.rule.id,
+                "value": finding.rule.id,
+            })];
+            if let Some(advisory) = &finding.advisory {
+                let url = if advisory.id.starts_with("RUSTSEC-") {
+                    format!("https://rustsec.org/advisories/{}", advisory.id)
+                } else {
+                    format!("https://osv.dev/vulnerability/{}", advisory.id)
+                };
+                identifiers.insert(
+                    0,
+                    serde_json::json!({
+                        "type": advisory.id.splitn(2, '-').next().unwrap_or_default().to_lowercase(),
+                        "name": advisory.id,
+                        "value": advisory.id,
+                        "url": url,
+                    }),
+                );
+            }
+            let mut vulnerability = serde_json::json!({
+                "id": format!("{:x}", id),
+                "name": format!("{}: {}", finding.rule.description, finding.package),
+                "description": finding.message,
+                "severity": gitlab_severity(finding),
+                "identifiers": identifiers,
+                "location": {
+                    "file": "Cargo.lock",
+                    "dependency": {
+                        "package": { "name": finding.package },
+                        "version": version,
+                    },
+                },
+            });
+            if let Some(advisory) = &finding.advisory {
+                if !advisory.patched_versions.is_empty() {
+                    vulnerability["solution"] = serde_json::json!(format!(
+                        "upgrade {} to {}",
+                        finding.package,
+                        advisory.patched_versions.join(" or ")
+                    ));
+                }
+            }
+            vulnerability
+        })
+        .collect();
+    for violation in violations {
+        let id = Sha256::digest(
+            format!(
+                "policy:{}:{}:{}",
+                violation.rule,
+                violation.package_id.as_deref().unwrap_or_default(),
+                violation.message
+            )
+            .as_bytes(),
+        );
+        let (name, version) = split_package_id(violation.package_id.as_deref().unwrap_or_default());
+        vulnerabilities.push(serde_json::json!({
+            "id": format!("{:x}", id),
+            "name": format!("policy violation: {}", violation.rule),
+            "description": violation.message,
+            "severity": if violation.in_baseline { "Info" } else { "High" },
+            "identifiers": [{
+                "type": "dephell_policy",
+                "name": violation.rule,
+                "value": violation.rule,
+            }],
+            "location": {
+                "file": "Cargo.lock",
+                "dependency": {
+                    "package": { "name": name },
+                    "version": version,
+                },
+            },
+        }));
+    }
+
+    let mut dependencies = Vec::new();
+    let mut packages: Vec<(&String, &PackageRisk)> = analysis_result
+        .iter()
+        .filter(|(_, package_risk)| !package_risk.internal)
+        .collect();
+    packages.sort_by(|a, b| a.0.cmp(b.0));
+    for (_, package_risk) in packages {
+        let mut versions: Vec<&String> = package_risk.versions.iter().collect();
+        versions.sort();
+        for version in versions {
+            dependencies.push(serde_json::json!({
+                "package": { "name": package_risk.name },
+                "version": version,
+            }));
+        }
+    }
+
+    let tool = serde_json::json!({
+        "id": "cargo-dephell",
+        "name": "cargo dephell",
+        "version": env!("CARGO_PKG_VERSION"),
+        "vendor": { "name": "cargo dephell" },
+    });
+    let time_format = "%Y-%m-%dT%H:%M:%S";
+    serde_json::json!({
+        "version": GITLAB_SCHEMA_VERSION,
+        "scan": {
+            "analyzer": tool,
+            "scanner": tool,
+            "type": "dependency_scanning",
+            "start_time": start_time.format(time_format).to_string(),
+            "end_time": end_time.format(time_format).to_string(),
+            "status": "success",
+        },
+        "vulnerabilities": vulnerabilities,
+        "dependency_files": [{
+            "path": "Cargo.lock",
+            "package_manager": "cargo",
+            "dependencies": dependencies,
+        }],
+    })
+}
+
+//
+// OpenMetrics
+// -----------
+// https://github.com/OpenObservability/OpenMetrics/blob/main/specification/OpenMetrics.md
+//
+
+/// escapes the value of a label
+fn label_escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// produces gauges about the dependency tree and each dependency, in the OpenMetrics format
+pub fn to_openmetrics(totals: &Totals, analysis_result: &BTreeMap<String, PackageRisk>) -> String {
+    let mut packages: Vec<(&String, &PackageRisk)> = analysis_result
+        .iter()
+        .filter(|(_, package_risk)| !package_risk.internal)
+        .collect();
+    packages.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut metrics = String::new();
+    let mut gauge = |name: &str, help: &str, samples: Vec<(Option<&PackageRisk>, u64)>| {
+        metrics.push_str(&format!("# TYPE dephell_{} gauge\n", name));
+        metrics.push_str(&format!("# HELP dephell_{} {}\n", name, help));
+        for (package_risk, value) in samples {
+            match package_risk {
+                Some(package_risk) => {
+                    let mut versions: Vec<&str> =
+                        package_risk.versions.iter().map(|v| v.as_str()).collect();
+                    versions.sort();
+                    metrics.push_str(&format!(
+                        "dephell_{}{{crate=\"{}\",version=\"{}\"}} {}\n",
+                        name,
+                        label_escape(&package_risk.name),
+                        label_escape(&versions.join(",")),
+                        value
+                    ))
+                }
+                None => metrics.push_str(&format!("dephell_{} {}\n", name, value)),
+            }
+        }
+    };
+
+    // totals
+    gauge(
+        "dependencies",
+        "number of third-party dependencies",
+        vec![(None, totals.dependencies)],
+    );
+    gauge(
+        "direct_dependencies",
+        "number of direct third-party dependencies",
+        vec![(None, totals.direct_dependencies)],
+    );
+    gauge(
+        "advisories",
+        "number of known advisories affecting dependencies",
+        vec![(None, totals.advisories)],
+    );
+    gauge(
+        "yanked",
+        "number of dependencies with a yanked version",
+        vec![(None, totals.yanked)],
+    );
+
+    // per dependency
+    let per_crate = |metric: fn(&PackageRisk) -> u64| -> Vec<(Option<&PackageRisk>, u64)> {
+        packages
+            .iter()
+            .map(|(_, package_risk)| (Some(*package_risk), metric(package_risk)))
+            .collect()
+    };
+    gauge(
+        "loc",
+        "lines of code of a dependency",
+        per_crate(|package_risk| package_risk.loc),
+    );
+    gauge(
+        "rust_loc",
+        "lines of rust code of a dependency",
+        per_crate(|package_risk| package_risk.rust_loc),
+    );
+    gauge(
+        "unsafe_loc",
+        "lines of unsafe rust code of a dependency",
+        per_crate(|package_risk| package_risk.unsafe_loc),
+    );
+    gauge(
+        "transitive_dependencies",
+        "number of dependencies a dependency pulls",
+        per_crate(|package_risk| package_risk.transitive_dependencies.len() as u64),
+    );
+    gauge(
+        "known_advisories",
+        "number of known advisories affecting a dependency",
+        per_crate(|package_risk| package_risk.known_advisories.len() as u64),
+    );
+
+    metrics.push_str("# EOF\n");
+    metrics
+}
+
+//
+// DOT
+// ---
+// to render the dependency graph with graphviz (`dot -Tsvg`)
+//
+
+/// escapes a string for a DOT quoted identifier
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// produces the dependency graph in the DOT format,
+/// nodes are colored by risk (advisories, unsafe code) and sized by lines of rust code
+pub fn to_dot(
+    name: &str,
+    main_dependencies: &BTreeSet<String>,
+    analysis_result: &BTreeMap<String, PackageRisk>,
+) -> String {
+    let mut packages: Vec<(&String, &PackageRisk)> = analysis_result.iter().collect();
+    packages.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut dot = String::from("digraph dependencies {\n");
+    dot.push_str("    rankdir=LR;\n");
+    dot.push_str("    node [shape=box, style=filled, fontname=\"sans-serif\"];\n");
+    dot.push_str(&format!(
+        "    \"{}\" [shape=doubleoctagon, fillcolor=\"#9ecae1\"];\n",
+        dot_escape(name)
+    ));
+
+    for (package_id, package_risk) in &packages {
+        let color = if package_risk.internal {
+            "#d9d9d9"
+        } else if !package_risk.known_advisories.is_empty() || package_risk.yanked {
+            "#fb6a4a"
+        } else if package_risk.unsafe_loc > findings::HIGH_UNSAFE_LOC {
+            "#fdae6b"
+        } else if package_risk.unsafe_loc > 0 {
+            "#fee391"
+        } else {
+            "#c7e9c0"
+        };
+        // the size grows with the order of magnitude of the rust code
+        let width = 1.0 + (package_risk.rust_loc as f64 + 1.0).log10() / 2.0;
+
+        let mut versions: Vec<&str> = package_risk.versions.iter().map(|v| v.as_str()).collect();
+        versions.sort();
+        let mut label = format!(
+            "{}\\n{}",
+            dot_escape(&package_risk.name),
+            versions.join(", ")
+        );
+        if package_risk.unsafe_loc > 0 {
+            label.push_str(&format!("\\nunsafe: {}", package_risk.unsafe_loc));
+        }
+        if !package_risk.known_advisories.is_empty() {
+            label.push_str(&format!(
+                "\\nadvisories: {}",
+                package_risk.known_advisories.len()
+            ));
+        }
+        if let Some(stars) = package_risk.stargazers_count {
+            label.push_str(&format!("\\nstars: {}", stars));
+        }
+        dot.push_str(&format!(
+            "    \"{}\" [label=\"{}\", fillcolor=\"{}\", width={:.2}];\n",
+            dot_escape(package_id),
+            label,
+            color,
+            width
+        ));
+    }
+
+    // edges
+    let mut main_dependencies: Vec<&String> = main_dependencies.iter().collect();
+    main_dependencies.sort();
+    for dependency in main_dependencies {
+        dot.push_str(&format!(
+            "    \"{}\" -> \"{}\";\n",
+            dot_escape(name),
+            dot_escape(dependency)
+        ));
+    }
+    for (package, package_risk) in &packages {
+        let mut dependencies: Vec<&String> = package_risk.direct_dependencies.iter().collect();
+        dependencies.sort();
+        for dependency in dependencies {
+            dot.push_str(&format!(
+                "    \"{}\" -> \"{}\";\n",
+                dot_escape(package),
+                dot_escape(dependency)
+            ));
+        }
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+//
+// Badges
+// ------
+// https://shields.io/badges/endpoint-badge
+//
+
+/// BadgeMetric is what a badge shows
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BadgeMetric {
+    /// number of third-party dependencies
+    Dependencies,
+    /// total lines of unsafe code in third-party dependencies
+    UnsafeLoc,
+}
+
+/// names accepted by `--badge-metric`
+pub const BADGE_METRICS: &[&str] = &["dependencies", "unsafe"];
+
+impl BadgeMetric {
+    /// parses a name from `BADGE_METRICS`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "dependencies" => Some(BadgeMetric::Dependencies),
+            "unsafe" => Some(BadgeMetric::UnsafeLoc),
+            _ => None,
+        }
+    }
+}
+
+/// returns the label, message and color (shields.io name and hex) of a badge
+fn badge(
+    metric: BadgeMetric,
+    totals: &Totals,
+) -> (&'static str, String, (&'static str, &'static str)) {
+    let (green, yellow, orange, red) = (
+        ("brightgreen", "#4c1"),
+        ("yellow", "#dfb317"),
+        ("orange", "#fe7d37"),
+        ("red", "#e05d44"),
+    );
+    match metric {
+        BadgeMetric::Dependencies => {
+            let color = match totals.dependencies {
+                0..=49 => green,
+                50..=149 => yellow,
+                150..=299 => orange,
+                _ => red,
+            };
+            ("dependencies", totals.dependencies.to_string(), color)
+        }
+        BadgeMetric::UnsafeLoc => {
+            let color = match totals.unsafe_loc {
+                0 => green,
+                loc if loc <= findings::HIGH_UNSAFE_LOC => yellow,
+                loc if loc <= 10 * findings::HIGH_UNSAFE_LOC => orange,
+                _ => red,
+            };
+            ("unsafe code", format!("{} lines", totals.unsafe_loc), color)
+        }
+    }
+}
+
+/// produces a shields.io endpoint (to use with `https://img.shields.io/endpoint?url=...`)
+pub fn to_shields_endpoint(metric: BadgeMetric, totals: &Totals) -> serde_json::Value {
+    let (label, message, (color, _)) = badge(metric, totals);
+    serde_json::json!({
+        "schemaVersion": 1,
+        "label": label,
+        "message": message,
+        "color": color,
+    })
+}
+
+/// produces an SVG badge, in the flat style of shields.io
+pub fn to_badge_svg(metric: BadgeMetric, totals: &Totals) -> String {
+    let (label, message, (_, color)) = badge(metric, totals);
+
+    // rough width of the text (11px Verdana is around 7px per character)
+    let text_width = |text: &str| text.chars().count() * 7 + 10;
+    let (label_width, message_width) = (text_width(label), text_width(&message));
+    let width = label_width + message_width;
+    let (label, message) = (xml_escape(label), xml_escape(&message));
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"20\" role=\"img\" aria-label=\"{}: {}\">\n",
+        width, label, message
+    );
+    svg.push_str(&format!("  <title>{}: {}</title>\n", label, message));
+    svg.push_str("  <linearGradient id=\"s\" x2=\"0\" y2=\"100%\"><stop offset=\"0\" stop-color=\"#bbb\" stop-opacity=\".1\"/><stop offset=\"1\" stop-opacity=\".1\"/></linearGradient>\n");
+    svg.push_str(&format!(
+        "  <clipPath id=\"r\"><rect width=\"{}\" height=\"20\" rx=\"3\" fill=\"#fff\"/></clipPath>\n",
+        width
+    ));
+    svg.push_str("  <g clip-path=\"url(#r)\">\n");
+    svg.push_str(&format!(
+        "    <rect width=\"{}\" height=\"20\" fill=\"#555\"/>\n",
+        label_width
+    ));
+    svg.push_str(&format!(
+        "    <rect x=\"{}\" width=\"{}\" height=\"20\" fill=\"{}\"/>\n",
+        label_width, message_width, color
+    ));
+    svg.push_str(&format!(
+        "    <rect width=\"{}\" height=\"20\" fill=\"url(#s)\"/>\n",
+        width
+    ));
+    svg.push_str("  </g>\n");
+    svg.push_str("  <g fill=\"#fff\" text-anchor=\"middle\" font-family=\"Verdana,Geneva,DejaVu Sans,sans-serif\" font-size=\"11\">\n");
+    for (x, text) in &[
+        (label_width as f64 / 2.0, &label),
+        (label_width as f64 + message_width as f64 / 2.0, &message),
+    ] {
+        svg.push_str(&format!(
+            "    <text x=\"{}\" y=\"15\" fill=\"#010101\" fill-opacity=\".3\">{}</text>\n",
+            x, text
+        ));
+        svg.push_str(&format!("    <text x=\"{}\" y=\"14\">{}</text>\n", x, text));
+    }
+    svg.push_str("  </g>\n");
+    svg.push_str("</svg>");
+    svg
+}
+
+//
+// Notices
+// -------
+// the attributions a product shipping the dependencies has to include
+//
+
+/// is this file a license (or a notice) that has to be reproduced?
+fn is_license_file(file_name: &str) -> bool {
+    let file_name = file_name.to_uppercase();
+    [
+        "LICENSE",
+        "LICENCE",
+        "COPYING",
+        "COPYRIGHT",
+        "NOTICE",
+        "UNLICENSE",
+    ]
+    .iter()
+    .any(|prefix| file_name.starts_with(prefix))
+}
+
+/// finds the license files of a package: at its root, and in a `LICENSES/` directory
+fn license_files(package_dir: &Path) -> Vec<std::path::PathBuf> {
+    let list = |dir: &Path, all: bool| -> Vec<std::path::PathBuf> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+        entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .filter(|path| {
+                all || path
+                    .file_name()
+                    .and_then(|file_name| file_name.to_str())
+                    .map(is_license_file)
+                    .unwrap_or(false)
+            })
+            .collect()
+    };
+    let mut files = list(package_dir, false);
+    files.extend(list(&package_dir.join("LICENSES"), true));
+    files.sort();
+    files
+}
+
+/// produces a THIRD-PARTY-NOTICES file: for each third-party dependency,
+/// its license, authors, and the license texts found in its package
+pub fn to_notices(name: &str, analysis_result: &BTreeMap<String, PackageRisk>) -> String {
+    let title = format!("THIRD-PARTY SOFTWARE NOTICES for {}", name);
+    let mut notices = format!("{}\n{}\n\n", title, "=".repeat(title.len()));
+    notices.push_str(&format!(
+        "{} includes the following third-party packages.\n",
+        name
+    ));
+
+    for (package_id, package_risk) in analysis_result {
+        if package_risk.internal {
+            continue;
+        }
+        notices.push_str(&format!("\n{}\n\n{}\n", "-".repeat(79), package_id));
+        notices.push_str(&format!(
+            "license: {}\n",
+            package_risk
+                .license
+                .as_deref()
+                .unwrap_or("unknown (no license in Cargo.toml)")
+        ));
+        if !package_risk.authors.is_empty() {
+            notices.push_str(&format!("authors: {}\n", package_risk.authors.join(", ")));
+        }
+        if let Some(repo) = &package_risk.repo {
+            notices.push_str(&format!("repository: {}\n", repo));
+        }
+
+        // the license texts
+        let package_dir = match package_risk.manifest_path.parent() {
+            Some(package_dir) => package_dir,
+            None => continue,
+        };
+        let files = license_files(package_dir);
+        if files.is_empty() {
+            notices.push_str("\n(no license file found in the package)\n");
+        }
+        for file in files {
+            let text = match fs::read_to_string(&file) {
+                Ok(text) => text,
+                Err(_) => continue,
+            };
+            let file_name = file.strip_prefix(package_dir).unwrap_or(&file);
+            notices.push_str(&format!("\n--- {} ---\n\n", file_name.display()));
+            notices.push_str(text.trim_end());
+            notices.push('\n');
+        }
+    }
+    notices
+}
@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+
+//
+// Review state
+// ============
+// the versions of the crates that have been signed off, and by whom, e.g.
+//
+// ```toml
+// [[reviewed]]
+// crate = "openssl-sys"
+// version = "0.9.58"
+// by = "alice"
+// ```
+//
+
+/// a `[[reviewed]]` entry
+#[derive(Deserialize)]
+struct SignOff {
+    #[serde(rename = "crate")]
+    crate_name: String,
+    version: String,
+    by: String,
+}
+
+#[derive(Deserialize)]
+struct ReviewStateFile {
+    #[serde(default)]
+    reviewed: Vec<SignOff>,
+}
+
+/// parses a review state file and returns, for every crate, the versions signed off and by whom
+pub fn parse_review_state(
+    review_state_path: &str,
+) -> Result<HashMap<String, BTreeMap<String, String>>, String> {
+    let contents = fs::read_to_string(review_state_path)
+        .map_err(|err| format!("dephell: could not read {}: {}", review_state_path, err))?;
+    let review_state_file: ReviewStateFile = toml::from_str(&contents)
+        .map_err(|err| format!("dephell: could not parse {}: {}", review_state_path, err))?;
+
+    let mut sign_offs: HashMap<String, BTreeMap<String, String>> = HashMap::new();
+    for sign_off in review_state_file.reviewed {
+        sign_offs
+            .entry(sign_off.crate_name)
+            .or_default()
+            .insert(sign_off.version, sign_off.by);
+    }
+    Ok(sign_offs)
+}
+
+/// has a crate been reviewed?
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SignOffStatus {
+    /// all the versions pulled were signed off
+    Reviewed,
+    /// other versions were signed off, the ones pulled need to be reviewed again
+    Bumped,
+    /// no version was ever signed off
+    New,
+}
+
+/// Review is where the review of a crate is at, according to the review state file
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Review {
+    pub status: SignOffStatus,
+    /// who signed off the versions pulled
+    pub reviewed_by: Vec<String>,
+    /// the versions signed off that are not pulled anymore
+    pub previous_versions: Vec<String>,
+}
+
+/// checks which of the versions of a crate that are pulled have been signed off
+pub fn get_review(
+    sign_offs: &HashMap<String, BTreeMap<String, String>>,
+    crate_name: &str,
+    versions: &BTreeSet<String>,
+) -> Review {
+    let signed_off = sign_offs.get(crate_name);
+    let reviewed_by: BTreeSet<String> = versions
+        .iter()
+        .filter_map(|version| signed_off.and_then(|signed_off| signed_off.get(version)))
+        .cloned()
+        .collect();
+    let previous_versions: Vec<String> = signed_off
+        .map(|signed_off| {
+            signed_off
+                .keys()
+                .filter(|version| !versions.contains(*version))
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default();
+    let all_signed_off = versions.iter().all(|version| {
+        signed_off
+            .map(|signed_off| signed_off.contains_key(version))
+            .unwrap_or(false)
+    });
+    let status = if all_signed_off {
+        SignOffStatus::Reviewed
+    } else if signed_off.is_some() {
+        SignOffStatus::Bumped
+    } else {
+        SignOffStatus::New
+    };
+    Review {
+        status,
+        reviewed_by: reviewed_by.into_iter().collect(),
+        previous_versions,
+    }
+}
@@ -0,0 +1,814 @@
+use quote::ToTokens;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use syn::visit::{self, Visit};
+
+//
+// Source Scanning
+// ===============
+// heuristics that look at the content of a dependency's files
+//
+
+/// extensions of files that are binary but expected (images, fonts, etc.)
+const BENIGN_BINARY_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "ico", "bmp", "webp", "woff", "woff2", "ttf", "otf", "eot",
+];
+
+/// extensions of precompiled native code
+const NATIVE_LIBRARY_EXTENSIONS: &[&str] = &["so", "dll", "dylib", "a", "lib", "o", "obj", "exe"];
+
+/// minimum length of a base64/hex literal to be considered an opaque blob
+const MIN_ENCODED_BLOB_LEN: usize = 1024;
+
+/// returns every file of a package (including hidden and ignored files, which might hide things:
+/// the package controls its `.gitignore` and `.ignore` files),
+/// except the `.git` directories of git checkouts and the `target` directory of a package built locally
+pub fn get_package_files(package_path: &Path) -> Vec<PathBuf> {
+    let target_dir = package_path.join("target");
+    let mut files: Vec<_> = ignore::WalkBuilder::new(package_path)
+        .standard_filters(false)
+        .filter_entry(move |entry| entry.file_name() != ".git" && entry.path() != target_dir)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().map(|ft| ft.is_file()).unwrap_or(false))
+        .map(|entry| entry.into_path())
+        .collect();
+    files.sort();
+    files
+}
+
+/// recognizes executable formats from their magic bytes
+fn executable_format(contents: &[u8]) -> Option<&'static str> {
+    match contents {
+        [0x7f, b'E', b'L', b'F', ..] => Some("ELF binary"),
+        [b'M', b'Z', ..] => Some("PE binary"),
+        [0xfe, 0xed, 0xfa, 0xce, ..]
+        | [0xfe, 0xed, 0xfa, 0xcf, ..]
+        | [0xce, 0xfa, 0xed, 0xfe, ..]
+        | [0xcf, 0xfa, 0xed, 0xfe, ..]
+        | [0xca, 0xfe, 0xba, 0xbe, ..] => Some("Mach-O binary"),
+        [b'!', b'<', b'a', b'r', b'c', b'h', b'>', ..] => Some("static library"),
+        [0x00, b'a', b's', b'm', ..] => Some("WebAssembly binary"),
+        _ => None,
+    }
+}
+
+/// finds long runs of base64 or hex characters (e.g. an embedded payload)
+fn has_encoded_blob(text: &str) -> bool {
+    let mut run = 0;
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() || c == '+' || c == '/' || c == '=' {
+            run += 1;
+            if run >= MIN_ENCODED_BLOB_LEN {
+                return true;
+            }
+        } else {
+            run = 0;
+        }
+    }
+    false
+}
+
+/// lists the binary and opaque files shipped in a package (with the reason they were flagged)
+pub fn get_binary_artifacts(package_path: &Path) -> Vec<String> {
+    let mut binary_artifacts = Vec::new();
+    for file in get_package_files(package_path) {
+        let relative_path = file
+            .strip_prefix(package_path)
+            .unwrap_or(&file)
+            .to_string_lossy()
+            .to_string();
+        let extension = file
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase())
+            .unwrap_or_default();
+        if BENIGN_BINARY_EXTENSIONS.contains(&extension.as_str()) {
+            continue;
+        }
+        let contents = match fs::read(&file) {
+            Ok(contents) => contents,
+            Err(err) => {
+                eprintln!("dephell: couldn't read {:?}: {}", file, err);
+                continue;
+            }
+        };
+
+        let reason = if let Some(format) = executable_format(&contents) {
+            Some(format)
+        } else if NATIVE_LIBRARY_EXTENSIONS.contains(&extension.as_str()) {
+            Some("native library")
+        } else if contents.iter().take(8192).any(|byte| *byte == 0) {
+            Some("opaque binary")
+        } else if has_encoded_blob(&String::from_utf8_lossy(&contents)) {
+            Some("large base64/hex literal")
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            binary_artifacts.push(format!("{} ({})", relative_path, reason));
+        }
+    }
+    binary_artifacts
+}
+
+/// extensions of source files that are checked for obfuscation
+const SCRIPT_EXTENSIONS: &[&str] = &[
+    "rs", "c", "cc", "cpp", "h", "hpp", "js", "py", "sh", "bash", "pl", "rb", "ps1", "bat",
+];
+
+/// lines longer than this are unlikely to be written by a human
+const MAX_LINE_LEN: usize = 1000;
+
+/// string literals longer than this are checked for entropy
+const MIN_ENTROPY_LITERAL_LEN: usize = 64;
+
+/// entropy (in bits per character) above which a string literal looks random/encrypted
+/// (english text is around 4, hex is at most 4, base64 is at most 6)
+const MAX_LITERAL_ENTROPY: f64 = 4.8;
+
+/// string literals containing more escaped bytes (`\x..`) than this look like a payload
+const MAX_ESCAPED_BYTES: usize = 64;
+
+/// computes the Shannon entropy of a string, in bits per character
+fn shannon_entropy(text: &str) -> f64 {
+    let mut frequencies = std::collections::HashMap::new();
+    let mut len = 0;
+    for c in text.chars() {
+        *frequencies.entry(c).or_insert(0usize) += 1;
+        len += 1;
+    }
+    frequencies
+        .values()
+        .map(|&count| {
+            let p = count as f64 / len as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// returns why a file's content looks obfuscated, if it does
+fn obfuscation_reason(contents: &str) -> Option<&'static str> {
+    if contents.lines().any(|line| line.len() > MAX_LINE_LEN) {
+        return Some("abnormally long lines");
+    }
+    let string_literal = Regex::new(r#""((?:[^"\\\n]|\\.)*)""#).unwrap();
+    for literal in string_literal.captures_iter(contents) {
+        let literal = &literal[1];
+        if literal.matches("\\x").count() > MAX_ESCAPED_BYTES {
+            return Some("dense escaped bytes");
+        }
+        if literal.len() >= MIN_ENTROPY_LITERAL_LEN
+            && shannon_entropy(literal) > MAX_LITERAL_ENTROPY
+        {
+            return Some("high-entropy string literal");
+        }
+    }
+    if has_encoded_blob(contents) {
+        return Some("large base64/hex literal");
+    }
+    None
+}
+
+/// lists the source files of a package that look obfuscated (with the reason they were flagged)
+pub fn get_suspicious_files(package_path: &Path) -> Vec<String> {
+    let mut suspicious_files = Vec::new();
+    for file in get_package_files(package_path) {
+        let extension = file
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase())
+            .unwrap_or_default();
+        if !SCRIPT_EXTENSIONS.contains(&extension.as_str()) {
+            continue;
+        }
+        // binary files are reported as binary artifacts
+        let contents = match fs::read_to_string(&file) {
+            Ok(contents) => contents,
+            Err(_) => continue,
+        };
+        if let Some(reason) = obfuscation_reason(&contents) {
+            let relative_path = file.strip_prefix(package_path).unwrap_or(&file);
+            suspicious_files.push(format!("{} ({})", relative_path.display(), reason));
+        }
+    }
+    suspicious_files
+}
+
+//
+// Build Script Analysis
+// =====================
+//
+
+/// BuildScriptRisk summarizes the dangerous operations performed by a build script.
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+pub struct BuildScriptRisk {
+    /// does the build script spawn processes (`std::process::Command`)?
+    pub spawns_processes: bool,
+    /// does the build script use networking (`std::net`, reqwest, curl, etc.)?
+    pub network_access: bool,
+    /// environment variables read that are not related to the build (tokens, keys, etc.)
+    pub sensitive_env_vars: Vec<String>,
+    /// does the build script write files outside of OUT_DIR?
+    pub writes_outside_out_dir: bool,
+}
+
+/// crates that can be used to access the network
+const NETWORK_CRATES: &[&str] = &[
+    "reqwest",
+    "curl",
+    "ureq",
+    "hyper",
+    "attohttpc",
+    "isahc",
+    "surf",
+    "ssh2",
+    "git2",
+];
+
+/// modules that give access to the network
+const NETWORK_MODULES: &[&str] = &["std::net", "async_std::net", "tokio::net", "mio::net"];
+
+/// substrings of environment variable names that are likely to contain secrets
+const SENSITIVE_ENV_VARS: &[&str] = &[
+    "TOKEN",
+    "SECRET",
+    "PASSWORD",
+    "PASSWD",
+    "KEY",
+    "CREDENTIAL",
+    "AWS_",
+    "GITHUB_",
+    "SSH_",
+];
+
+/// functions writing to the filesystem, and the position of the argument that is written to
+const FS_WRITES: &[(&str, usize)] = &[
+    ("fs::write", 0),
+    ("File::create", 0),
+    ("fs::copy", 1),
+    ("fs::rename", 1),
+    ("fs::remove_file", 0),
+    ("fs::remove_dir_all", 0),
+];
+
+/// converts a path to a string (e.g. `std::process::Command`)
+fn path_to_string(path: &syn::Path) -> String {
+    path.segments
+        .iter()
+        .map(|segment| segment.ident.to_string())
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// lists the paths imported by a `use` tree, with where they are
+/// (e.g. `use std::{io, net::TcpStream as Stream}` imports `std::io` and `std::net::TcpStream`)
+fn use_tree_paths(tree: &syn::UseTree, prefix: &str, paths: &mut Vec<(String, proc_macro2::Span)>) {
+    match tree {
+        syn::UseTree::Path(path) => {
+            let prefix = format!("{}{}::", prefix, path.ident);
+            use_tree_paths(&path.tree, &prefix, paths);
+        }
+        syn::UseTree::Name(name) => {
+            paths.push((format!("{}{}", prefix, name.ident), name.ident.span()))
+        }
+        syn::UseTree::Rename(rename) => {
+            paths.push((format!("{}{}", prefix, rename.ident), rename.ident.span()))
+        }
+        syn::UseTree::Glob(glob) => paths.push((format!("{}*", prefix), glob.star_token.spans[0])),
+        syn::UseTree::Group(group) => {
+            for tree in &group.items {
+                use_tree_paths(tree, prefix, paths);
+            }
+        }
+    }
+}
+
+struct BuildScriptVisitor {
+    risk: BuildScriptRisk,
+}
+
+impl BuildScriptVisitor {
+    fn check_network(&mut self, path_str: &str) {
+        let first_segment = path_str.split("::").next().unwrap_or_default();
+        if path_str.contains("net::")
+            || path_str.contains("TcpStream")
+            || path_str.contains("UdpSocket")
+            || NETWORK_CRATES.contains(&first_segment)
+        {
+            self.risk.network_access = true;
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for BuildScriptVisitor {
+    fn visit_path(&mut self, path: &'ast syn::Path) {
+        let path_str = path_to_string(path);
+        if path_str.contains("process::Command") || path_str.ends_with("Command::new") {
+            self.risk.spawns_processes = true;
+        }
+        self.check_network(&path_str);
+        visit::visit_path(self, path);
+    }
+
+    fn visit_item_use(&mut self, item_use: &'ast syn::ItemUse) {
+        // what's imported (even renamed, or in a group) is checked as if it was used
+        let mut paths = Vec::new();
+        use_tree_paths(&item_use.tree, "", &mut paths);
+        for (path_str, _) in paths {
+            self.check_network(&format!("{}::", path_str));
+        }
+        visit::visit_item_use(self, item_use);
+    }
+
+    fn visit_use_path(&mut self, use_path: &'ast syn::UsePath) {
+        let ident = use_path.ident.to_string();
+        if NETWORK_CRATES.contains(&ident.as_str()) || ident == "net" {
+            self.risk.network_access = true;
+        }
+        if ident == "process" {
+            self.risk.spawns_processes = true;
+        }
+        visit::visit_use_path(self, use_path);
+    }
+
+    fn visit_item_extern_crate(&mut self, extern_crate: &'ast syn::ItemExternCrate) {
+        if NETWORK_CRATES.contains(&extern_crate.ident.to_string().as_str()) {
+            self.risk.network_access = true;
+        }
+        visit::visit_item_extern_crate(self, extern_crate);
+    }
+
+    fn visit_expr_call(&mut self, call: &'ast syn::ExprCall) {
+        if let syn::Expr::Path(func) = &*call.func {
+            let path_str = path_to_string(&func.path);
+
+            // reading environment variables
+            if path_str.ends_with("env::var") || path_str.ends_with("env::var_os") {
+                if let Some(syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(var),
+                    ..
+                })) = call.args.first()
+                {
+                    let var = var.value();
+                    let sensitive = SENSITIVE_ENV_VARS
+                        .iter()
+                        .any(|sensitive| var.to_uppercase().contains(sensitive));
+                    if sensitive && !self.risk.sensitive_env_vars.contains(&var) {
+                        self.risk.sensitive_env_vars.push(var);
+                    }
+                }
+            }
+
+            // writing files (best effort: the destination should mention OUT_DIR)
+            for (fs_write, arg_position) in FS_WRITES {
+                if !path_str.ends_with(fs_write) {
+                    continue;
+                }
+                if let Some(destination) = call.args.iter().nth(*arg_position) {
+                    let destination = destination.to_token_stream().to_string().to_lowercase();
+                    if !destination.contains("out") {
+                        self.risk.writes_outside_out_dir = true;
+                    }
+                }
+            }
+        }
+        visit::visit_expr_call(self, call);
+    }
+}
+
+/// parses a build script and looks for dangerous operations
+pub fn get_build_script_risk(build_script: &Path) -> Option<BuildScriptRisk> {
+    let contents = match fs::read_to_string(build_script) {
+        Ok(contents) => contents,
+        Err(err) => {
+            eprintln!("dephell: couldn't read {:?}: {}", build_script, err);
+            return None;
+        }
+    };
+    let file = match syn::parse_file(&contents) {
+        Ok(file) => file,
+        Err(err) => {
+            eprintln!("dephell: couldn't parse {:?}: {}", build_script, err);
+            return None;
+        }
+    };
+    let mut visitor = BuildScriptVisitor {
+        risk: BuildScriptRisk::default(),
+    };
+    visitor.visit_file(&file);
+    Some(visitor.risk)
+}
+
+//
+// Native Code
+// ===========
+//
+
+/// NativeCode describes the native (C/C++) code a package links to or bundles.
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+pub struct NativeCode {
+    /// is the package named like a -sys crate?
+    pub sys_crate: bool,
+    /// the native library declared via the `links` manifest key
+    pub links: Option<String>,
+    /// native libraries vendored in the package
+    pub bundled_libraries: Vec<BundledLibrary>,
+}
+
+/// BundledLibrary is a native library vendored in a package.
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+pub struct BundledLibrary {
+    /// name of the library (usually the name of its folder)
+    pub name: String,
+    /// path of the library inside the package
+    pub path: String,
+    /// version of the library (if found in its headers)
+    pub version: Option<String>,
+}
+
+/// extensions of C/C++ files
+const NATIVE_SOURCE_EXTENSIONS: &[&str] = &["c", "cc", "cpp", "cxx", "h", "hh", "hpp", "s"];
+
+/// folders that usually contain vendored libraries
+const VENDOR_FOLDERS: &[&str] = &[
+    "vendor",
+    "vendored",
+    "third_party",
+    "third-party",
+    "deps",
+    "bundled",
+    "src",
+];
+
+/// looks for a version string in the headers of a bundled library (e.g. `#define ZLIB_VERSION "1.2.11"`)
+fn get_bundled_library_version(headers: &[PathBuf]) -> Option<String> {
+    let re =
+        Regex::new(r#"#\s*define\s+\w*VERSION\w*\s+"(?:\w+ )?([0-9]+\.[0-9]+[0-9A-Za-z.\-]*)"#)
+            .unwrap();
+    for header in headers {
+        let contents = match fs::read_to_string(header) {
+            Ok(contents) => contents,
+            Err(_) => continue,
+        };
+        if let Some(version) = re.captures(&contents).and_then(|caps| caps.get(1)) {
+            return Some(version.as_str().to_string());
+        }
+    }
+    None
+}
+
+/// detects -sys crates, `links` keys and vendored C/C++ libraries
+pub fn get_native_code(
+    package_name: &str,
+    links: Option<&str>,
+    package_path: &Path,
+) -> Option<NativeCode> {
+    // group C/C++ files by the folder of the library they belong to
+    let mut libraries: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+    for file in get_package_files(package_path) {
+        let is_native = file
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| NATIVE_SOURCE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+            .unwrap_or(false);
+        if !is_native {
+            continue;
+        }
+        let relative_path = match file.strip_prefix(package_path) {
+            Ok(relative_path) => relative_path.to_path_buf(),
+            Err(_) => continue,
+        };
+        // `vendor/openssl/...` -> `vendor/openssl`, `zlib/...` -> `zlib`
+        let mut library_path = PathBuf::new();
+        for component in relative_path.parent().unwrap_or(Path::new("")).components() {
+            library_path.push(component);
+            let component = component.as_os_str().to_string_lossy();
+            if !VENDOR_FOLDERS.contains(&component.as_ref()) {
+                break;
+            }
+        }
+        libraries.entry(library_path).or_default().push(file);
+    }
+
+    let bundled_libraries: Vec<BundledLibrary> = libraries
+        .into_iter()
+        .map(|(library_path, files)| {
+            let headers: Vec<PathBuf> = files
+                .into_iter()
+                .filter(|file| file.extension().map(|ext| ext == "h").unwrap_or(false))
+                .collect();
+            // files directly in `src/` or `vendor/` are attributed to the package itself
+            let name = library_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .filter(|name| !VENDOR_FOLDERS.contains(&name.as_str()))
+                .unwrap_or_else(|| package_name.to_string());
+            BundledLibrary {
+                name,
+                path: library_path.to_string_lossy().to_string(),
+                version: get_bundled_library_version(&headers),
+            }
+        })
+        .collect();
+
+    let sys_crate = package_name.ends_with("-sys") || package_name.ends_with("_sys");
+    if !sys_crate && links.is_none() && bundled_libraries.is_empty() {
+        return None;
+    }
+    Some(NativeCode {
+        sys_crate,
+        links: links.map(|links| links.to_string()),
+        bundled_libraries,
+    })
+}
+
+//
+// Capabilities
+// ============
+// what can a dependency do at runtime?
+//
+
+/// is an item only compiled for tests (`#[cfg(test)]`)?
+pub fn is_cfg_test(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path.is_ident("cfg") && attr.tokens.to_string().replace(' ', "") == "(test)"
+    })
+}
+
+/// the network capability: sockets, HTTP clients, etc.
+pub const NETWORK: &str = "network";
+/// the process capability: spawning processes with `std::process::Command`
+pub const PROCESS: &str = "process";
+/// the exec capability: replacing or forking the current process via libc
+pub const EXEC: &str = "exec";
+/// the filesystem capability: writing, moving or removing files
+pub const FILESYSTEM_WRITE: &str = "filesystem_write";
+
+/// libc functions that execute programs
+const EXEC_FUNCTIONS: &[&str] = &["exec", "system", "fork", "vfork", "posix_spawn", "popen"];
+
+/// functions that write to, or remove from, the filesystem
+const FILESYSTEM_WRITE_FUNCTIONS: &[&str] = &[
+    "fs::write",
+    "fs::remove_file",
+    "fs::remove_dir",
+    "fs::remove_dir_all",
+    "fs::rename",
+    "fs::copy",
+    "File::create",
+    "OpenOptions",
+];
+
+/// CapabilityVisitor looks for code using sensitive capabilities in a rust file.
+struct CapabilityVisitor<'a> {
+    /// the file being analyzed (relative to the package)
+    file: &'a str,
+    /// has `std::process` been imported in this file?
+    imports_process: bool,
+    /// locations (`file:line`) of code using each capability
+    evidence: BTreeMap<&'static str, Vec<String>>,
+}
+
+impl<'a> CapabilityVisitor<'a> {
+    fn record(&mut self, capability: &'static str, span: proc_macro2::Span) {
+        let location = format!("{}:{}", self.file, span.start().line);
+        let evidence = self.evidence.entry(capability).or_default();
+        if !evidence.contains(&location) {
+            evidence.push(location);
+        }
+    }
+
+    fn check_path(&mut self, path_str: &str, span: proc_macro2::Span) {
+        let first_segment = path_str.split("::").next().unwrap_or_default();
+
+        // network
+        if NETWORK_MODULES
+            .iter()
+            .any(|module| path_str.starts_with(module))
+            || path_str.contains("TcpStream")
+            || path_str.contains("TcpListener")
+            || path_str.contains("UdpSocket")
+            || path_str.contains("libc::socket")
+            || path_str.contains("libc::connect")
+            || NETWORK_CRATES.contains(&first_segment)
+        {
+            self.record(NETWORK, span);
+        }
+
+        // process
+        if path_str.starts_with("std::process") {
+            self.imports_process = true;
+        }
+        if path_str.contains("process::Command")
+            || (self.imports_process && path_str.ends_with("Command::new"))
+        {
+            self.record(PROCESS, span);
+        }
+
+        // exec
+        if path_str.starts_with("libc::") {
+            let function = &path_str["libc::".len()..];
+            if EXEC_FUNCTIONS.iter().any(|exec| function.starts_with(exec)) {
+                self.record(EXEC, span);
+            }
+        }
+
+        // filesystem
+        if FILESYSTEM_WRITE_FUNCTIONS
+            .iter()
+            .any(|function| path_str.ends_with(function))
+        {
+            self.record(FILESYSTEM_WRITE, span);
+        }
+    }
+}
+
+impl<'a, 'ast> Visit<'ast> for CapabilityVisitor<'a> {
+    fn visit_path(&mut self, path: &'ast syn::Path) {
+        if let Some(first_segment) = path.segments.first() {
+            self.check_path(&path_to_string(path), first_segment.ident.span());
+        }
+        visit::visit_path(self, path);
+    }
+
+    fn visit_item_use(&mut self, item_use: &'ast syn::ItemUse) {
+        // what's imported (even renamed, or in a group) is checked with its full path
+        let mut paths = Vec::new();
+        use_tree_paths(&item_use.tree, "", &mut paths);
+        for (path_str, span) in paths {
+            self.check_path(&path_str, span);
+        }
+        visit::visit_item_use(self, item_use);
+    }
+
+    fn visit_item_extern_crate(&mut self, extern_crate: &'ast syn::ItemExternCrate) {
+        self.check_path(&extern_crate.ident.to_string(), extern_crate.ident.span());
+        visit::visit_item_extern_crate(self, extern_crate);
+    }
+
+    // skip tests
+    fn visit_item_mod(&mut self, item_mod: &'ast syn::ItemMod) {
+        if !is_cfg_test(&item_mod.attrs) {
+            visit::visit_item_mod(self, item_mod);
+        }
+    }
+
+    fn visit_item_fn(&mut self, item_fn: &'ast syn::ItemFn) {
+        if !is_cfg_test(&item_fn.attrs) {
+            visit::visit_item_fn(self, item_fn);
+        }
+    }
+}
+
+/// checks if a crate root forbids (or denies) unsafe code via `#![forbid(unsafe_code)]`
+pub fn forbids_unsafe(crate_root: &Path) -> bool {
+    let ast = match fs::read_to_string(crate_root)
+        .ok()
+        .and_then(|contents| syn::parse_file(&contents).ok())
+    {
+        Some(ast) => ast,
+        None => return false,
+    };
+    ast.attrs.iter().any(|attr| {
+        (attr.path.is_ident("forbid") || attr.path.is_ident("deny"))
+            && attr.tokens.to_string().contains("unsafe_code")
+    })
+}
+
+/// attributes that control the symbols exported by a binary
+const SYMBOL_EXPORT_ATTRIBUTES: &[&str] = &["no_mangle", "export_name", "link_section"];
+
+/// SymbolExportVisitor looks for items exporting symbols (`#[no_mangle]`, etc.)
+struct SymbolExportVisitor<'a> {
+    /// the file being analyzed (relative to the package)
+    file: &'a str,
+    /// `file:line #[attribute] item` entries
+    symbol_exports: Vec<String>,
+}
+
+impl<'a> SymbolExportVisitor<'a> {
+    fn check_item(&mut self, attrs: &[syn::Attribute], ident: &syn::Ident) {
+        for attr in attrs {
+            let is_symbol_export = SYMBOL_EXPORT_ATTRIBUTES
+                .iter()
+                .any(|export| attr.path.is_ident(export));
+            if is_symbol_export {
+                let attr_str = attr.to_token_stream().to_string().replace(' ', "");
+                self.symbol_exports.push(format!(
+                    "{}:{} {} {}",
+                    self.file,
+                    ident.span().start().line,
+                    attr_str,
+                    ident
+                ));
+            }
+        }
+    }
+}
+
+impl<'a, 'ast> Visit<'ast> for SymbolExportVisitor<'a> {
+    fn visit_item_fn(&mut self, item_fn: &'ast syn::ItemFn) {
+        self.check_item(&item_fn.attrs, &item_fn.sig.ident);
+        visit::visit_item_fn(self, item_fn);
+    }
+
+    fn visit_impl_item_method(&mut self, method: &'ast syn::ImplItemMethod) {
+        self.check_item(&method.attrs, &method.sig.ident);
+        visit::visit_impl_item_method(self, method);
+    }
+
+    fn visit_item_static(&mut self, item_static: &'ast syn::ItemStatic) {
+        self.check_item(&item_static.attrs, &item_static.ident);
+        visit::visit_item_static(self, item_static);
+    }
+
+    // skip tests
+    fn visit_item_mod(&mut self, item_mod: &'ast syn::ItemMod) {
+        if !is_cfg_test(&item_mod.attrs) {
+            visit::visit_item_mod(self, item_mod);
+        }
+    }
+}
+
+/// lists the items of a package that export symbols (`#[no_mangle]`, `#[export_name]`, `#[link_section]`)
+pub fn get_symbol_exports(rust_files: &[RustFile]) -> Vec<String> {
+    let mut symbol_exports = Vec::new();
+    for rust_file in rust_files {
+        let mut visitor = SymbolExportVisitor {
+            file: &rust_file.path,
+            symbol_exports: Vec::new(),
+        };
+        visitor.visit_file(&rust_file.ast);
+        symbol_exports.extend(visitor.symbol_exports);
+    }
+    symbol_exports
+}
+
+/// RustFile is a parsed rust file of a package.
+pub struct RustFile {
+    /// path of the file (relative to the package)
+    pub path: String,
+    /// content of the file
+    pub contents: String,
+    /// parsed content of the file
+    pub ast: syn::File,
+}
+
+/// parses the rust files of a package (skips the files that can't be parsed)
+pub fn parse_rust_files(package_path: &Path, dependency_files: &HashSet<String>) -> Vec<RustFile> {
+    let mut rust_files: Vec<&String> = dependency_files
+        .iter()
+        .filter(|file| file.ends_with(".rs"))
+        .collect();
+    rust_files.sort();
+    let mut parsed_files = Vec::new();
+    for rust_file in rust_files {
+        let contents = match fs::read_to_string(rust_file) {
+            Ok(contents) => contents,
+            Err(err) => {
+                eprintln!("dephell: couldn't read {}: {}", rust_file, err);
+                continue;
+            }
+        };
+        let ast = match syn::parse_file(&contents) {
+            Ok(ast) => ast,
+            Err(err) => {
+                eprintln!("dephell: couldn't parse {}: {}", rust_file, err);
+                continue;
+            }
+        };
+        let path = Path::new(rust_file)
+            .strip_prefix(package_path)
+            .unwrap_or(Path::new(rust_file))
+            .to_string_lossy()
+            .to_string();
+        parsed_files.push(RustFile {
+            path,
+            contents,
+            ast,
+        });
+    }
+    parsed_files
+}
+
+/// looks for sensitive capabilities in the rust files of a package,
+/// returns the locations (`file:line`) of the code using each capability
+pub fn get_capabilities(rust_files: &[RustFile]) -> BTreeMap<&'static str, Vec<String>> {
+    let mut capabilities: BTreeMap<&'static str, Vec<String>> = BTreeMap::new();
+    for rust_file in rust_files {
+        let mut visitor = CapabilityVisitor {
+            file: &rust_file.path,
+            imports_process: false,
+            evidence: BTreeMap::new(),
+        };
+        visitor.visit_file(&rust_file.ast);
+        for (capability, evidence) in visitor.evidence {
+            capabilities.entry(capability).or_default().extend(evidence);
+        }
+    }
+    capabilities
+}
@@ -0,0 +1,98 @@
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::output::{self, Context, Format};
+
+//
+// Report server
+// =============
+// `cargo dephell serve` serves the report on localhost,
+// instead of writing it to a file that has to be opened
+//
+
+/// the content type of a format, when served
+fn content_type(format: Format) -> &'static str {
+    match format {
+        Format::Html => "text/html; charset=utf-8",
+        Format::Json | Format::Gitlab | Format::Sarif | Format::CycloneDx | Format::Shields => {
+            "application/json"
+        }
+        Format::Csv | Format::Inventory => "text/csv; charset=utf-8",
+        Format::Junit => "application/xml",
+        Format::Badge => "image/svg+xml",
+        _ => "text/plain; charset=utf-8",
+    }
+}
+
+/// is the request addressed to us? (a page can't read the report by rebinding its domain to localhost)
+fn is_local_host(request: &Request, port: u16) -> bool {
+    let allowed = [format!("localhost:{}", port), format!("127.0.0.1:{}", port)];
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Host"))
+        .map_or(false, |host| {
+            allowed
+                .iter()
+                .any(|allowed| host.value.as_str().eq_ignore_ascii_case(allowed))
+        })
+}
+
+/// serves the report on `localhost:port` until interrupted:
+/// the HTML output on `/`, and the JSON report on `/api/report` (or any other format with `?format=NAME`).
+/// The report is rendered again on every request.
+pub fn serve(port: u16, context: &Context) -> Result<(), String> {
+    let server = Server::http(("127.0.0.1", port)).map_err(|err| err.to_string())?;
+    eprintln!(
+        "dephell: serving the report on http://localhost:{} (the JSON on /api/report), press Ctrl+C to stop",
+        port
+    );
+
+    for request in server.incoming_requests() {
+        let url = request.url().to_owned();
+        if !is_local_host(&request, port) {
+            let response = Response::from_string("forbidden").with_status_code(403);
+            if let Err(err) = request.respond(response) {
+                eprintln!("dephell: couldn't answer a request for {}: {}", url, err);
+            }
+            continue;
+        }
+        let (path, query) = match url.find('?') {
+            Some(idx) => (&url[..idx], &url[idx + 1..]),
+            None => (url.as_str(), ""),
+        };
+        let format = match path {
+            "/" | "/index.html" => Some(Format::Html),
+            "/api/report" => {
+                let format_name = query
+                    .split('&')
+                    .find(|param| param.starts_with("format="))
+                    .map(|param| &param["format=".len()..]);
+                match format_name {
+                    None => Some(Format::Json),
+                    Some(format_name) => Format::from_name(format_name),
+                }
+            }
+            _ => None,
+        };
+        let response = match format {
+            None => Response::from_string("not found").with_status_code(404),
+            // JSON lines are streamed during the analysis, only the last line could be rendered here
+            Some(Format::Jsonl) => {
+                Response::from_string("the jsonl format can't be served, use json")
+                    .with_status_code(400)
+            }
+            Some(format) => match output::render(format, context) {
+                Err(err) => Response::from_string(err).with_status_code(500),
+                Ok(body) => {
+                    let header = Header::from_bytes(&b"Content-Type"[..], content_type(format))
+                        .expect("content types are valid headers");
+                    Response::from_string(body).with_header(header)
+                }
+            },
+        };
+        if let Err(err) = request.respond(response) {
+            eprintln!("dephell: couldn't answer a request for {}: {}", url, err);
+        }
+    }
+    Ok(())
+}
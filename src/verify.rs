@@ -0,0 +1,260 @@
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, Instant};
+use tempdir::TempDir;
+
+use crate::http::HttpClient;
+
+//
+// Source Verification
+// ===================
+// compares the code published on crates.io with the code on the repository
+//
+
+/// SourceVerification is the result of comparing a .crate file with its repository.
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+pub struct SourceVerification {
+    /// the git revision that was checked out (commit or tag)
+    pub revision: String,
+    /// files that are different in the .crate and in the repository
+    pub differing_files: Vec<String>,
+    /// files that only exist in the .crate
+    pub tarball_only_files: Vec<String>,
+}
+
+/// files that are generated (or rewritten) by `cargo publish`
+const GENERATED_FILES: &[&str] = &[
+    ".cargo_vcs_info.json",
+    "Cargo.toml",
+    "Cargo.toml.orig",
+    "Cargo.lock",
+];
+
+/// the `.cargo_vcs_info.json` file included by `cargo publish`
+#[derive(Deserialize, Debug)]
+struct VcsInfo {
+    git: GitInfo,
+    path_in_vcs: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitInfo {
+    sha1: String,
+}
+
+/// downloads the .crate file and extracts it, returns the path to the extracted crate
+fn download_crate(
+    http_client: HttpClient,
+    crate_name: &str,
+    version: &str,
+    destination: &Path,
+) -> Result<PathBuf, String> {
+    let request_url = format!(
+        "https://crates.io/api/v1/crates/{}/{}/download",
+        crate_name, version
+    );
+    let resp = http_client
+        .get(&request_url)
+        .send()
+        .map_err(|err| err.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("crates.io download failed ({})", resp.status()));
+    }
+    let bytes = resp.bytes().map_err(|err| err.to_string())?;
+    let mut archive = tar::Archive::new(GzDecoder::new(&bytes[..]));
+    archive.unpack(destination).map_err(|err| err.to_string())?;
+    // .crate files contain a single `<name>-<version>` folder
+    Ok(destination.join(format!("{}-{}", crate_name, version)))
+}
+
+/// how long listing the tags of a repository can take
+const LS_REMOTE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// runs a command, and kills it if it takes longer than `timeout`
+fn output_with_timeout(mut command: Command, timeout: Duration) -> Result<Output, String> {
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| err.to_string())?;
+    // the output is read as it comes, so that the command doesn't block on a full pipe
+    let mut stdout = child.stdout.take().unwrap();
+    let reader = std::thread::spawn(move || {
+        let mut output = Vec::new();
+        stdout.read_to_end(&mut output).map(|_| output)
+    });
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(|err| err.to_string())? {
+            break status;
+        }
+        if start.elapsed() > timeout {
+            child.kill().ok();
+            child.wait().ok();
+            return Err(format!("timed out after {}s", timeout.as_secs()));
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    };
+    let stdout = reader
+        .join()
+        .map_err(|_| "could not read the output".to_owned())?
+        .map_err(|err| err.to_string())?;
+    Ok(Output {
+        status,
+        stdout,
+        stderr: Vec::new(),
+    })
+}
+
+/// the tags commonly used to mark the release of a version
+/// (the last two are used by monorepos)
+fn version_tags(crate_name: &str, version: &str) -> Vec<String> {
+    vec![
+        format!("v{}", version),
+        version.to_owned(),
+        format!("{}-v{}", crate_name, version),
+        format!("{}-{}", crate_name, version),
+    ]
+}
+
+/// checks if the repository has a tag for the version, without cloning it
+pub fn find_version_tag(repo_url: &str, crate_name: &str, version: &str) -> Result<bool, String> {
+    // the URL comes from the package, so it can't be taken as an option
+    let mut command = Command::new("git");
+    command
+        .args(&["ls-remote", "--tags", "--", repo_url])
+        .env("GIT_TERMINAL_PROMPT", "0");
+    let output = output_with_timeout(command, LS_REMOTE_TIMEOUT)
+        .map_err(|err| format!("could not list the tags of {}: {}", repo_url, err))?;
+    if !output.status.success() {
+        return Err(format!("could not list the tags of {}", repo_url));
+    }
+    // lines are of the form "<sha>\trefs/tags/<tag>" (or "<tag>^{}" for annotated tags)
+    let candidates = version_tags(crate_name, version);
+    let found = String::from_utf8_lossy(&output.stdout).lines().any(|line| {
+        line.split("refs/tags/")
+            .nth(1)
+            .map(|tag| tag.trim_end_matches("^{}"))
+            .map(|tag| candidates.iter().any(|candidate| candidate == tag))
+            .unwrap_or(false)
+    });
+    Ok(found)
+}
+
+/// clones a repository and checks out the first revision that exists
+fn checkout_repo(
+    repo_url: &str,
+    revisions: &[String],
+    destination: &Path,
+) -> Result<String, String> {
+    // the URL and the revisions come from the package, so they can't be taken as options,
+    // and git can't ask for credentials (e.g. for a private or deleted repository)
+    let output = Command::new("git")
+        .args(&["clone", "--quiet", "--", repo_url])
+        .arg(destination)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .map_err(|err| err.to_string())?;
+    if !output.status.success() {
+        return Err(format!("could not clone {}", repo_url));
+    }
+    for revision in revisions
+        .iter()
+        .filter(|revision| !revision.starts_with('-'))
+    {
+        let output = Command::new("git")
+            .args(&["checkout", "--quiet", revision, "--"])
+            .current_dir(destination)
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .output()
+            .map_err(|err| err.to_string())?;
+        if output.status.success() {
+            return Ok(revision.to_owned());
+        }
+    }
+    Err(format!("none of the revisions {:?} exist", revisions))
+}
+
+/// returns every file in a directory (relative to that directory)
+fn list_files(directory: &Path) -> HashSet<PathBuf> {
+    ignore::WalkBuilder::new(directory)
+        .standard_filters(false)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().map(|ft| ft.is_file()).unwrap_or(false))
+        .filter_map(|entry| {
+            entry
+                .path()
+                .strip_prefix(directory)
+                .ok()
+                .map(|path| path.to_path_buf())
+        })
+        .collect()
+}
+
+/// downloads the published .crate, checks out the claimed repository at the matching revision,
+/// and diffs the two
+pub fn verify_source(
+    http_client: HttpClient,
+    crate_name: &str,
+    version: &str,
+    repo_url: &str,
+) -> Result<SourceVerification, String> {
+    let workdir = TempDir::new("dephell_verify").map_err(|err| err.to_string())?;
+
+    // get the published code
+    let crate_dir = download_crate(http_client, crate_name, version, workdir.path())?;
+
+    // figure out what revision to check out
+    let vcs_info: Option<VcsInfo> = fs::read_to_string(crate_dir.join(".cargo_vcs_info.json"))
+        .ok()
+        .and_then(|vcs_info| serde_json::from_str(&vcs_info).ok());
+    let mut revisions = Vec::new();
+    if let Some(vcs_info) = &vcs_info {
+        revisions.push(vcs_info.git.sha1.clone());
+    }
+    revisions.extend(version_tags(crate_name, version));
+
+    // get the code on the repository
+    let repo_dir = workdir.path().join("repo");
+    let revision = checkout_repo(repo_url, &revisions, &repo_dir)?;
+    let repo_crate_dir = match vcs_info.and_then(|vcs_info| vcs_info.path_in_vcs) {
+        Some(path_in_vcs) => repo_dir.join(path_in_vcs),
+        None => repo_dir,
+    };
+
+    // diff
+    let mut verification = SourceVerification {
+        revision,
+        ..Default::default()
+    };
+    let mut tarball_files: Vec<PathBuf> = list_files(&crate_dir).into_iter().collect();
+    tarball_files.sort();
+    for file in tarball_files {
+        let file_str = file.to_string_lossy().to_string();
+        // Cargo.toml is rewritten on publish, the original is Cargo.toml.orig
+        let repo_file = if file_str == "Cargo.toml.orig" {
+            repo_crate_dir.join("Cargo.toml")
+        } else if GENERATED_FILES.contains(&file_str.as_str()) {
+            continue;
+        } else {
+            repo_crate_dir.join(&file)
+        };
+        match (fs::read(crate_dir.join(&file)), fs::read(&repo_file)) {
+            (Ok(published), Ok(upstream)) => {
+                if published != upstream {
+                    verification.differing_files.push(file_str);
+                }
+            }
+            _ => verification.tarball_only_files.push(file_str),
+        }
+    }
+
+    //
+    Ok(verification)
+}
@@ -0,0 +1,105 @@
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+//
+// cargo-vet
+// =========
+// see https://mozilla.github.io/cargo-vet/audit-entries.html
+//
+
+/// an entry of the `[[audits.<crate>]]` tables
+#[derive(Deserialize)]
+struct AuditEntry {
+    /// a full audit of a version
+    version: Option<String>,
+    /// a delta audit of the form "1.0.0 -> 1.1.0"
+    delta: Option<String>,
+    /// a version requirement that must NOT be used
+    violation: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AuditsFile {
+    #[serde(default)]
+    audits: HashMap<String, Vec<AuditEntry>>,
+}
+
+/// parses a cargo-vet `audits.toml` and returns, for every crate, the versions that are vetted
+/// (the `criteria` of the audits are ignored: a `safe-to-run` audit counts as much as a `safe-to-deploy` one),
+/// minus the versions matching a `violation`
+pub fn parse_audits(audits_path: &str) -> Result<HashMap<String, HashSet<String>>, String> {
+    let contents = fs::read_to_string(audits_path)
+        .map_err(|err| format!("dephell: could not read {}: {}", audits_path, err))?;
+    let audits_file: AuditsFile = toml::from_str(&contents)
+        .map_err(|err| format!("dephell: could not parse {}: {}", audits_path, err))?;
+
+    let mut vetted_versions = HashMap::new();
+    for (crate_name, entries) in audits_file.audits {
+        // start with full audits
+        let mut versions: HashSet<String> = entries
+            .iter()
+            .filter(|entry| entry.violation.is_none())
+            .filter_map(|entry| entry.version.clone())
+            .collect();
+
+        // then follow delta audits from an already-vetted version
+        let deltas: Vec<(&str, &str)> = entries
+            .iter()
+            .filter_map(|entry| entry.delta.as_ref())
+            .filter_map(|delta| {
+                let mut split = delta.split("->");
+                match (split.next(), split.next()) {
+                    (Some(from), Some(to)) => Some((from.trim(), to.trim())),
+                    _ => None,
+                }
+            })
+            .collect();
+        loop {
+            let new_versions: Vec<String> = deltas
+                .iter()
+                .filter(|(from, to)| versions.contains(*from) && !versions.contains(*to))
+                .map(|(_, to)| to.to_string())
+                .collect();
+            if new_versions.is_empty() {
+                break;
+            }
+            versions.extend(new_versions);
+        }
+
+        // remove the versions that must not be used
+        for violation in entries.iter().filter_map(|entry| entry.violation.as_ref()) {
+            match semver::VersionReq::parse(violation) {
+                Ok(violation) => versions.retain(|version| {
+                    semver::Version::parse(version)
+                        .map(|version| !violation.matches(&version))
+                        .unwrap_or(false)
+                }),
+                Err(err) => {
+                    eprintln!(
+                        "dephell: couldn't parse the violation {} of {} in {}, none of its versions are vetted: {}",
+                        violation, crate_name, audits_path, err
+                    );
+                    versions.clear();
+                }
+            }
+        }
+
+        vetted_versions.insert(crate_name, versions);
+    }
+
+    //
+    Ok(vetted_versions)
+}
+
+/// checks if a specific version of a crate has been vetted
+pub fn is_vetted(
+    vetted_versions: &HashMap<String, HashSet<String>>,
+    crate_name: &str,
+    version: &str,
+) -> bool {
+    vetted_versions
+        .get(crate_name)
+        .map(|versions| versions.contains(version))
+        .unwrap_or(false)
+}